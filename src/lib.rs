@@ -1 +1,16 @@
-extern crate noise;
\ No newline at end of file
+//! Библиотека Unistone.
+//!
+//! Генерация мира доступна отдельно от игры через модуль [`world`],
+//! поэтому ее могут использовать утилиты, тесты и сама игра.
+//!
+//! Возможности библиотеки разделены на cargo features:
+//! - `world` (по умолчанию) — генерация мира без графики;
+//! - `client` (по умолчанию) — рендер, выбор объектов, глобальное освещение и камера
+//!   игрового клиента на Bevy.
+//!
+//! Сборка без GPU: `cargo build --no-default-features --features world`.
+
+mod resource;
+
+#[cfg(feature = "world")]
+pub use resource::world;
@@ -1,4 +1,3 @@
-mod resource;
 mod system;
 
 use bevy::prelude::*;
@@ -6,7 +5,11 @@ use bevy_mod_picking::PickableBundle;
 use std::f32::consts::PI;
 
 use crate::system::camera::Camera;
+use crate::system::lod::LodTerrainPlugin;
+use crate::system::terrain::{TerrainPlugin, TerrainSettings};
+use crate::system::water::WaterPlugin;
 use crate::system::window::WindowPlugin;
+use crate::system::worldgen::WorldGenPlugin;
 
 fn main() {
     App::new()
@@ -14,6 +17,15 @@ fn main() {
         .add_plugins(DefaultPlugins)
         .insert_resource(Msaa { samples: 4 })
         .add_plugin(Camera)
+        .add_plugin(WorldGenPlugin)
+        // Ландшафт показывается участками, единая сетка не нужна
+        .insert_resource(TerrainSettings {
+            subdivisions: None,
+            ..Default::default()
+        })
+        .add_plugin(TerrainPlugin)
+        .add_plugin(LodTerrainPlugin)
+        .add_plugin(WaterPlugin)
         .insert_resource(ClearColor(Color::BLUE))
         .add_startup_system(setup)
         .run();
@@ -29,13 +41,6 @@ fn setup(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    // plane
-    commands.spawn_bundle(PbrBundle {
-        mesh: meshes.add(Mesh::from(shape::Plane { size: 1000.0 })),
-        material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
-        ..Default::default()
-    });
-
     //Target 1
     commands
         .spawn_bundle(PbrBundle {
@@ -0,0 +1,2 @@
+#[cfg(feature = "world")]
+pub mod world;
@@ -0,0 +1,596 @@
+//! Модуль биомов.
+//!
+//! Биом определяется по высоте относительно уровней моря и шельфа, маске
+//! бесплодных земель из графа континентов, крутизне склона и климату. С
+//! посчитанным [`Climate`] пояса суши выбираются по температуре и влажности.
+//! Без него климат заменяют широтные пояса: от влажных лесов у экватора через
+//! пустыни субтропиков к тайге и тундре, а высота над морем охлаждает
+//! местность так же, как сдвиг к полюсу.
+//!
+//! Крутизна склона измеряется в планетарных единицах высоты на градус дуги.
+
+use std::f64::consts::PI;
+
+use strum::EnumCount;
+use strum_macros::{Display, EnumCount, EnumIter, EnumString};
+
+use crate::resource::world::shading::neighbours;
+use crate::resource::world::{
+    Climate, Color, GeoBounds, Heightmap, MapImage, Planet, PlanetParams, TerrainLayer,
+};
+
+/// Шаг в градусах, с которым считается крутизна склона по планете.
+const SLOPE_STEP: f64 = 0.1;
+
+/// Широтные пояса суши: биом до указанной широты.
+const LATITUDE_BANDS: [(f64, Biome); 6] = [
+    (10.0, Biome::Rainforest),
+    (18.0, Biome::Savanna),
+    (32.0, Biome::Desert),
+    (42.0, Biome::Grassland),
+    (55.0, Biome::Forest),
+    (66.0, Biome::Taiga),
+];
+
+/// Температура в °C, выше которой в горах растет лес.
+const TREE_LINE_TEMPERATURE: f64 = 6.0;
+
+/// Тип местности.
+///
+/// Название биома в `snake_case`, например `"deep_ocean"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumCount, EnumIter, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum Biome {
+    /// Океан глубже шельфа.
+    DeepOcean,
+    /// Мелководье континентального шельфа.
+    Shelf,
+    /// Морской лед в полярных водах.
+    SeaIce,
+    /// Пологий берег у самой воды.
+    Beach,
+    Grassland,
+    Forest,
+    Rainforest,
+    Savanna,
+    Desert,
+    Tundra,
+    Taiga,
+    /// Бесплодные земли из графа континентов.
+    Badlands,
+    /// Высокогорье и крутые склоны.
+    Alpine,
+    /// Вечные снега и ледники.
+    Glacier,
+}
+
+impl Biome {
+    /// Биом под водой.
+    pub fn is_water(self) -> bool {
+        matches!(self, Self::DeepOcean | Self::Shelf | Self::SeaIce)
+    }
+}
+
+/// Цвета биомов для карт и сеток.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BiomePalette {
+    colors: [Color; Biome::COUNT],
+}
+
+impl BiomePalette {
+    /// Функция позволяющая указать цвет биома `biome`.
+    pub fn set_color(mut self, biome: Biome, color: Color) -> Self {
+        self.colors[biome as usize] = color;
+        self
+    }
+
+    pub fn color(&self, biome: Biome) -> Color {
+        self.colors[biome as usize]
+    }
+}
+
+impl Default for BiomePalette {
+    fn default() -> Self {
+        let mut colors = [[0; 3]; Biome::COUNT];
+        for (biome, color) in [
+            (Biome::DeepOcean, [5, 48, 105]),
+            (Biome::Shelf, [14, 112, 192]),
+            (Biome::SeaIce, [210, 230, 240]),
+            (Biome::Beach, [228, 214, 160]),
+            (Biome::Grassland, [128, 168, 78]),
+            (Biome::Forest, [58, 118, 54]),
+            (Biome::Rainforest, [28, 92, 40]),
+            (Biome::Savanna, [178, 170, 92]),
+            (Biome::Desert, [222, 194, 132]),
+            (Biome::Tundra, [150, 156, 124]),
+            (Biome::Taiga, [64, 102, 80]),
+            (Biome::Badlands, [176, 104, 64]),
+            (Biome::Alpine, [134, 124, 114]),
+            (Biome::Glacier, [245, 248, 252]),
+        ] {
+            colors[biome as usize] = color;
+        }
+
+        Self { colors }
+    }
+}
+
+/// Классификатор биомов планеты.
+///
+/// Высоты суши задаются долей от 0.0 (уровень моря) до 1.0 (высшая точка
+/// планеты), поэтому одни настройки подходят планетам с разным уровнем моря.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BiomeClassifier {
+    params: PlanetParams,
+    beach_height: f64,
+    alpine_height: f64,
+    glacier_height: f64,
+    steep_slope: f64,
+    altitude_cooling: f64,
+    polar_latitude: f64,
+    sea_ice_latitude: f64,
+    glacier_temperature: f64,
+    sea_ice_temperature: f64,
+}
+
+impl BiomeClassifier {
+    /// Создание классификатора для планеты с параметрами `params`.
+    pub fn new(params: &PlanetParams) -> Self {
+        Self {
+            params: *params,
+            beach_height: 0.01,
+            alpine_height: 0.3,
+            glacier_height: 0.45,
+            steep_slope: 0.3,
+            altitude_cooling: 60.0,
+            polar_latitude: 66.0,
+            sea_ice_latitude: 75.0,
+            glacier_temperature: -25.0,
+            sea_ice_temperature: -20.0,
+        }
+    }
+
+    /// Функция позволяющая указать высоту суши, до которой тянутся пляжи.
+    pub fn set_beach_height(mut self, beach_height: f64) -> Self {
+        self.beach_height = beach_height;
+        self
+    }
+
+    /// Функция позволяющая указать высоту суши, с которой начинается высокогорье на экваторе.
+    pub fn set_alpine_height(mut self, alpine_height: f64) -> Self {
+        self.alpine_height = alpine_height;
+        self
+    }
+
+    /// Функция позволяющая указать снеговую линию на экваторе.
+    /// К полюсам она опускается до уровня моря.
+    pub fn set_glacier_height(mut self, glacier_height: f64) -> Self {
+        self.glacier_height = glacier_height;
+        self
+    }
+
+    /// Функция позволяющая указать крутизну, с которой склон считается скалистым.
+    pub fn set_steep_slope(mut self, steep_slope: f64) -> Self {
+        self.steep_slope = steep_slope;
+        self
+    }
+
+    /// Функция позволяющая указать, на сколько градусов широты к полюсу
+    /// сдвигает пояс подъем от уровня моря до высшей точки планеты.
+    pub fn set_altitude_cooling(mut self, altitude_cooling: f64) -> Self {
+        self.altitude_cooling = altitude_cooling;
+        self
+    }
+
+    /// Функция позволяющая указать широту полярного круга: за ней на равнинах тундра.
+    pub fn set_polar_latitude(mut self, polar_latitude: f64) -> Self {
+        self.polar_latitude = polar_latitude;
+        self
+    }
+
+    /// Функция позволяющая указать широту, с которой море покрыто льдом.
+    pub fn set_sea_ice_latitude(mut self, sea_ice_latitude: f64) -> Self {
+        self.sea_ice_latitude = sea_ice_latitude;
+        self
+    }
+
+    /// Функция позволяющая указать температуру в °C, ниже которой суша покрыта льдом.
+    /// Используется вместе с климатом.
+    pub fn set_glacier_temperature(mut self, glacier_temperature: f64) -> Self {
+        self.glacier_temperature = glacier_temperature;
+        self
+    }
+
+    /// Функция позволяющая указать температуру в °C, ниже которой море покрыто льдом.
+    /// Используется вместе с климатом.
+    pub fn set_sea_ice_temperature(mut self, sea_ice_temperature: f64) -> Self {
+        self.sea_ice_temperature = sea_ice_temperature;
+        self
+    }
+
+    pub fn params(&self) -> &PlanetParams {
+        &self.params
+    }
+
+    pub fn beach_height(&self) -> f64 {
+        self.beach_height
+    }
+
+    pub fn alpine_height(&self) -> f64 {
+        self.alpine_height
+    }
+
+    pub fn glacier_height(&self) -> f64 {
+        self.glacier_height
+    }
+
+    pub fn steep_slope(&self) -> f64 {
+        self.steep_slope
+    }
+
+    pub fn altitude_cooling(&self) -> f64 {
+        self.altitude_cooling
+    }
+
+    pub fn polar_latitude(&self) -> f64 {
+        self.polar_latitude
+    }
+
+    pub fn sea_ice_latitude(&self) -> f64 {
+        self.sea_ice_latitude
+    }
+
+    pub fn glacier_temperature(&self) -> f64 {
+        self.glacier_temperature
+    }
+
+    pub fn sea_ice_temperature(&self) -> f64 {
+        self.sea_ice_temperature
+    }
+
+    /// Биом точки с высотой `elevation`, значением слоя
+    /// [`TerrainLayer::BadlandsMask`] `badlands`, широтой `lat` и крутизной `slope`.
+    pub fn classify(&self, elevation: f64, badlands: f64, lat: f64, slope: f64) -> Biome {
+        let lat = lat.abs();
+
+        if elevation < self.params.sea_level {
+            return if lat >= self.sea_ice_latitude {
+                Biome::SeaIce
+            } else if elevation < self.params.shelf_level {
+                Biome::DeepOcean
+            } else {
+                Biome::Shelf
+            };
+        }
+
+        // Высота суши и холод от 0.0 на экваторе у моря до 1.0 на полюсе
+        let height = (elevation - self.params.sea_level) / (1.0 - self.params.sea_level);
+        let cold = ((lat + height * self.altitude_cooling) / 90.0).min(1.0);
+        let warmth = 1.0 - cold * cold;
+
+        if height >= self.glacier_height * warmth {
+            Biome::Glacier
+        } else if height >= self.alpine_height * warmth || slope >= self.steep_slope {
+            Biome::Alpine
+        } else if height < self.beach_height {
+            Biome::Beach
+        } else if badlands > 1.0 - self.params.badlands_amount {
+            Biome::Badlands
+        } else {
+            let lat = cold * 90.0;
+            LATITUDE_BANDS
+                .iter()
+                .find(|(limit, _)| lat < *limit && lat < self.polar_latitude)
+                .map_or(Biome::Tundra, |(_, biome)| *biome)
+        }
+    }
+
+    /// Биом точки с высотой `elevation`, значением слоя [`TerrainLayer::BadlandsMask`]
+    /// `badlands`, крутизной `slope`, температурой `temperature` в °C и влажностью
+    /// `moisture` от 0.0 до 1.0.
+    pub fn classify_climate(
+        &self,
+        elevation: f64,
+        badlands: f64,
+        slope: f64,
+        temperature: f64,
+        moisture: f64,
+    ) -> Biome {
+        if elevation < self.params.sea_level {
+            return if temperature < self.sea_ice_temperature {
+                Biome::SeaIce
+            } else if elevation < self.params.shelf_level {
+                Biome::DeepOcean
+            } else {
+                Biome::Shelf
+            };
+        }
+
+        let height = (elevation - self.params.sea_level) / (1.0 - self.params.sea_level);
+
+        if temperature < self.glacier_temperature {
+            Biome::Glacier
+        } else if slope >= self.steep_slope
+            || (height >= self.alpine_height && temperature < TREE_LINE_TEMPERATURE)
+        {
+            Biome::Alpine
+        } else if height < self.beach_height {
+            Biome::Beach
+        } else if badlands > 1.0 - self.params.badlands_amount {
+            Biome::Badlands
+        } else {
+            climate_zone(temperature, moisture)
+        }
+    }
+
+    /// Карта биомов по карте высот `heightmap` и карте слоя
+    /// [`TerrainLayer::BadlandsMask`] `badlands` того же размера.
+    pub fn classify_map(&self, heightmap: &Heightmap, badlands: &Heightmap) -> BiomeMap {
+        self.map(heightmap, badlands, |x, y, elevation, badlands, slope| {
+            let (lat, _) = heightmap.lat_lon(x, y);
+            self.classify(elevation, badlands, lat, slope)
+        })
+    }
+
+    /// Карта биомов по карте высот `heightmap`, карте слоя
+    /// [`TerrainLayer::BadlandsMask`] `badlands` и климату `climate` на той же сетке.
+    pub fn classify_map_with_climate(
+        &self,
+        heightmap: &Heightmap,
+        badlands: &Heightmap,
+        climate: &Climate,
+    ) -> BiomeMap {
+        assert_eq!(
+            (heightmap.width(), heightmap.height()),
+            (
+                climate.temperature().width(),
+                climate.temperature().height()
+            ),
+            "climate does not match the heightmap"
+        );
+
+        self.map(heightmap, badlands, |x, y, elevation, badlands, slope| {
+            self.classify_climate(
+                elevation,
+                badlands,
+                slope,
+                climate.temperature().get(x, y) as f64,
+                climate.moisture().get(x, y) as f64,
+            )
+        })
+    }
+
+    /// Обход пикселей карты высот с крутизной склона в каждом из них.
+    fn map(
+        &self,
+        heightmap: &Heightmap,
+        badlands: &Heightmap,
+        mut classify: impl FnMut(usize, usize, f64, f64, f64) -> Biome,
+    ) -> BiomeMap {
+        assert_eq!(
+            (heightmap.width(), heightmap.height()),
+            (badlands.width(), badlands.height()),
+            "badlands mask does not match the heightmap"
+        );
+
+        let (width, height, bounds) = (heightmap.width(), heightmap.height(), heightmap.bounds());
+        let pixel_width = bounds.lon_extent() / width as f64;
+        let pixel_height = bounds.lat_extent() / height as f64;
+
+        let mut biomes = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (lat, _) = heightmap.lat_lon(x, y);
+                let [west, east, north, south] = neighbours(heightmap, x, y);
+
+                let slope = f64::hypot(
+                    (east - west) / (2.0 * pixel_width * lat.to_radians().cos().max(0.01)),
+                    (north - south) / (2.0 * pixel_height),
+                );
+                biomes.push(classify(
+                    x,
+                    y,
+                    heightmap.get(x, y) as f64,
+                    badlands.get(x, y) as f64,
+                    slope,
+                ));
+            }
+        }
+
+        BiomeMap {
+            width,
+            height,
+            bounds,
+            biomes,
+        }
+    }
+}
+
+/// Карта биомов. Сетка та же, что у карты высот, по которой она построена.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BiomeMap {
+    width: usize,
+    height: usize,
+    bounds: GeoBounds,
+    biomes: Vec<Biome>,
+}
+
+impl BiomeMap {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn bounds(&self) -> GeoBounds {
+        self.bounds
+    }
+
+    /// Биомы построчно, с севера на юг.
+    pub fn biomes(&self) -> &[Biome] {
+        &self.biomes
+    }
+
+    /// Биом в пикселе (`x`, `y`).
+    pub fn get(&self, x: usize, y: usize) -> Biome {
+        self.biomes[y * self.width + x]
+    }
+
+    /// Цветная карта биомов.
+    pub fn render(&self, palette: &BiomePalette) -> MapImage {
+        let pixels = self
+            .biomes
+            .iter()
+            .map(|biome| palette.color(*biome))
+            .collect();
+
+        MapImage::from_raw(self.width, self.height, pixels)
+    }
+}
+
+/// Пояс суши по температуре в °C и влажности, как на диаграмме Уиттекера.
+fn climate_zone(temperature: f64, moisture: f64) -> Biome {
+    match (temperature, moisture) {
+        (t, _) if t < 0.0 => Biome::Tundra,
+        (t, m) if t < TREE_LINE_TEMPERATURE => {
+            if m < 0.2 {
+                Biome::Tundra
+            } else {
+                Biome::Taiga
+            }
+        }
+        (_, m) if m < 0.2 => Biome::Desert,
+        (t, m) if t < 18.0 => {
+            if m < 0.45 {
+                Biome::Grassland
+            } else {
+                Biome::Forest
+            }
+        }
+        (_, m) if m < 0.5 => Biome::Savanna,
+        _ => Biome::Rainforest,
+    }
+}
+
+impl Planet {
+    /// Биом в точке с широтой `lat` и долготой `lon` (в градусах).
+    pub fn biome(&self, classifier: &BiomeClassifier, lat: f64, lon: f64) -> Biome {
+        classifier.classify(
+            self.elevation(lat, lon),
+            self.sample_layer(TerrainLayer::BadlandsMask, lat, lon),
+            lat,
+            self.slope(lat, lon),
+        )
+    }
+
+    /// Крутизна склона в точке с широтой `lat` и долготой `lon`
+    /// в планетарных единицах высоты на градус дуги.
+    pub fn slope(&self, lat: f64, lon: f64) -> f64 {
+        let lon_step = SLOPE_STEP / lat.to_radians().cos().max(0.01);
+
+        f64::hypot(
+            (self.elevation(lat, lon + lon_step) - self.elevation(lat, lon - lon_step))
+                / (2.0 * SLOPE_STEP),
+            (self.elevation(lat + SLOPE_STEP, lon) - self.elevation(lat - SLOPE_STEP, lon))
+                / (2.0 * SLOPE_STEP),
+        )
+    }
+}
+
+/// Крутизна склона по наклону нормали поверхности к вертикали.
+/// `cos_tilt` — косинус угла наклона, `exaggeration` — во сколько раз рельеф
+/// поверхности выше планетарных единиц высоты относительно радиуса.
+pub fn slope_from_tilt(cos_tilt: f64, exaggeration: f64) -> f64 {
+    if exaggeration <= 0.0 {
+        return 0.0;
+    }
+
+    let cos_tilt = cos_tilt.clamp(1e-6, 1.0);
+    let tan_tilt = (1.0 - cos_tilt * cos_tilt).sqrt() / cos_tilt;
+
+    tan_tilt / exaggeration * PI / 180.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    fn classifier() -> BiomeClassifier {
+        BiomeClassifier::new(&PlanetParams::default())
+    }
+
+    #[test]
+    fn classify_by_latitude() {
+        let classifier = classifier();
+
+        // Высота, маска бесплодных земель, широта, крутизна
+        for (elevation, badlands, lat, slope, expected) in [
+            (-0.8, 0.0, 10.0, 0.0, Biome::DeepOcean),
+            (-0.2, 0.0, 10.0, 0.0, Biome::Shelf),
+            (-0.8, 0.0, 80.0, 0.0, Biome::SeaIce),
+            (-0.2, 0.0, -80.0, 0.0, Biome::SeaIce),
+            (0.9, 0.0, 10.0, 0.0, Biome::Glacier),
+            (0.1, 0.0, 10.0, 0.5, Biome::Alpine),
+            (0.005, 0.0, 10.0, 0.0, Biome::Beach),
+            (0.1, 0.9, 10.0, 0.0, Biome::Badlands),
+            (0.1, 0.5, 10.0, 0.0, Biome::Savanna),
+            (0.05, 0.0, 70.0, 0.0, Biome::Tundra),
+        ] {
+            assert_eq!(
+                classifier.classify(elevation, badlands, lat, slope),
+                expected,
+                "elevation {}, badlands {}, latitude {}, slope {}",
+                elevation,
+                badlands,
+                lat,
+                slope
+            );
+        }
+    }
+
+    #[test]
+    fn classify_by_climate() {
+        let classifier = classifier();
+
+        // Высота, маска бесплодных земель, крутизна, температура, влажность
+        for (elevation, badlands, slope, temperature, moisture, expected) in [
+            (-0.8, 0.0, 0.0, 10.0, 1.0, Biome::DeepOcean),
+            (-0.2, 0.0, 0.0, 10.0, 1.0, Biome::Shelf),
+            (-0.8, 0.0, 0.0, -25.0, 1.0, Biome::SeaIce),
+            (0.1, 0.0, 0.0, -30.0, 0.5, Biome::Glacier),
+            (0.1, 0.0, 0.5, 10.0, 0.5, Biome::Alpine),
+            (0.5, 0.0, 0.0, 2.0, 0.5, Biome::Alpine),
+            (0.005, 0.0, 0.0, 15.0, 0.5, Biome::Beach),
+            (0.1, 0.9, 0.0, 15.0, 0.5, Biome::Badlands),
+            (0.1, 0.0, 0.0, 25.0, 0.1, Biome::Desert),
+            (0.1, 0.0, 0.0, 25.0, 0.8, Biome::Rainforest),
+            (0.1, 0.0, 0.0, 3.0, 0.5, Biome::Taiga),
+        ] {
+            assert_eq!(
+                classifier.classify_climate(elevation, badlands, slope, temperature, moisture),
+                expected,
+                "elevation {}, badlands {}, slope {}, temperature {}, moisture {}",
+                elevation,
+                badlands,
+                slope,
+                temperature,
+                moisture
+            );
+        }
+    }
+
+    #[test]
+    fn every_biome_has_color() {
+        let palette = BiomePalette::default();
+        let colors: HashSet<_> = Biome::iter().map(|biome| palette.color(biome)).collect();
+
+        assert!(!colors.contains(&[0, 0, 0]));
+        assert_eq!(colors.len(), Biome::COUNT);
+    }
+}
@@ -0,0 +1,357 @@
+//! Модуль климата.
+//!
+//! Климат считается по готовой карте высот:
+//! - температура зависит от широты и падает с высотой суши над морем;
+//! - влажность приносят преобладающие ветра с океана. Над сушей воздух
+//!   постепенно высыхает, а на подъеме к горам выпадают осадки, поэтому за
+//!   хребтами остается дождевая тень. Побережья влажные при любом ветре,
+//!   а нисходящий воздух субтропиков сушит пояса около 30° широты.
+//!
+//! Ветра дуют вдоль параллелей: пассаты у экватора и полярные ветра — с востока
+//! на запад, умеренные западные ветра — с запада на восток.
+//! Расстояния измеряются в градусах дуги.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use strum_macros::{Display, EnumCount, EnumIter, EnumString};
+
+use crate::resource::world::heightmap::{Grid, OrderedFloat};
+use crate::resource::world::shading::wraps_around;
+use crate::resource::world::{Heightmap, PlanetParams};
+
+/// Параметры климата.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClimateConfig {
+    /// Температура на экваторе у моря в °C.
+    pub equator_temperature: f64,
+    /// Температура на полюсе у моря в °C.
+    pub pole_temperature: f64,
+    /// На сколько °C холоднее на высшей точке планеты, чем у моря.
+    pub lapse_rate: f64,
+    /// Граница пассатов и западных ветров в градусах широты.
+    pub trade_wind_limit: f64,
+    /// Граница западных и полярных ветров в градусах широты.
+    pub westerlies_limit: f64,
+    /// Расстояние от берега, на котором влажность побережья падает в e раз.
+    pub coast_range: f64,
+    /// Расстояние над сушей, на котором ветер теряет в e раз больше влаги.
+    pub inland_range: f64,
+    /// Потеря влаги при подъеме: подъем на 1.0 планетарную единицу высоты выше
+    /// всего пройденного от берега оставляет в воздухе `e^-rain_shadow` влаги.
+    pub rain_shadow: f64,
+    /// Доля влаги, которую отнимает нисходящий воздух на 30° широты.
+    pub subtropical_drying: f64,
+}
+
+impl Default for ClimateConfig {
+    fn default() -> Self {
+        Self {
+            equator_temperature: 28.0,
+            pole_temperature: -30.0,
+            lapse_rate: 50.0,
+            trade_wind_limit: 30.0,
+            westerlies_limit: 60.0,
+            coast_range: 4.0,
+            inland_range: 45.0,
+            rain_shadow: 6.0,
+            subtropical_drying: 0.5,
+        }
+    }
+}
+
+/// Слой климата.
+///
+/// Название слоя в `snake_case`, например `"temperature"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumCount, EnumIter, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum ClimateLayer {
+    /// Среднегодовая температура в °C.
+    Temperature,
+    /// Влажность от 0.0 (сухо) до 1.0 (океан).
+    Moisture,
+}
+
+/// Климат планеты на сетке карты высот, по которой он посчитан.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Climate {
+    temperature: Heightmap,
+    moisture: Heightmap,
+}
+
+impl Climate {
+    /// Климат по карте высот `heightmap` планеты с параметрами `params`.
+    pub fn simulate(heightmap: &Heightmap, params: &PlanetParams, config: &ClimateConfig) -> Self {
+        let (width, height, bounds) = (heightmap.width(), heightmap.height(), heightmap.bounds());
+
+        let mut temperature = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (lat, _) = heightmap.lat_lon(x, y);
+                let elevation = heightmap.get(x, y) as f64;
+                temperature.push(surface_temperature(config, params, elevation, lat) as f32);
+            }
+        }
+
+        let ocean_distance = ocean_distance(heightmap, params);
+        let mut moisture = vec![0.0; width * height];
+        for y in 0..height {
+            let (lat, _) = heightmap.lat_lon(0, y);
+            let row = y * width..(y + 1) * width;
+
+            let wind = wind_moisture(
+                config,
+                params,
+                &heightmap.data()[row.clone()],
+                &ocean_distance[row.clone()],
+                lat,
+                bounds.lon_extent() / width as f64 * lat.to_radians().cos().max(0.01),
+                wraps_around(heightmap),
+            );
+
+            // Нисходящий воздух субтропиков
+            let drying = 1.0
+                - config.subtropical_drying
+                    * (-((lat.abs() - config.trade_wind_limit) / 10.0).powi(2)).exp();
+
+            for (x, wind) in wind.into_iter().enumerate() {
+                let cell = y * width + x;
+                moisture[cell] = if ocean_distance[cell] == 0.0 {
+                    1.0
+                } else {
+                    let coast = (-ocean_distance[cell] / config.coast_range).exp();
+                    (wind.max(coast) * drying) as f32
+                };
+            }
+        }
+
+        Self {
+            temperature: Heightmap::from_raw(width, height, bounds, temperature),
+            moisture: Heightmap::from_raw(width, height, bounds, moisture),
+        }
+    }
+
+    /// Среднегодовая температура в °C.
+    pub fn temperature(&self) -> &Heightmap {
+        &self.temperature
+    }
+
+    /// Влажность от 0.0 до 1.0.
+    pub fn moisture(&self) -> &Heightmap {
+        &self.moisture
+    }
+
+    pub fn layer(&self, layer: ClimateLayer) -> &Heightmap {
+        match layer {
+            ClimateLayer::Temperature => &self.temperature,
+            ClimateLayer::Moisture => &self.moisture,
+        }
+    }
+
+    /// Значение слоя `layer` в ближайшем к точке (`lat`, `lon`) пикселе.
+    /// `None`, если точка вне границ карты.
+    pub fn sample(&self, layer: ClimateLayer, lat: f64, lon: f64) -> Option<f64> {
+        let layer = self.layer(layer);
+        let (x, y) = layer.pixel_at(lat, lon)?;
+
+        Some(layer.get(x, y) as f64)
+    }
+}
+
+/// Температура у поверхности. Над водой температура берется на уровне моря.
+fn surface_temperature(
+    config: &ClimateConfig,
+    params: &PlanetParams,
+    elevation: f64,
+    lat: f64,
+) -> f64 {
+    let polar = lat.to_radians().sin().powi(2);
+    let sea_level =
+        config.equator_temperature + (config.pole_temperature - config.equator_temperature) * polar;
+
+    let height = ((elevation - params.sea_level) / (1.0 - params.sea_level)).max(0.0);
+    sea_level - config.lapse_rate * height
+}
+
+/// Влажность, которую приносит ветер в каждую клетку строки `elevations`.
+///
+/// Воздух идет по строке по ветру. Над океаном он насыщается, над сушей
+/// высыхает с расстоянием. Поднимаясь выше всего, что он пересек от берега,
+/// воздух проливает влагу на наветренный склон и дальше несет меньше.
+fn wind_moisture(
+    config: &ClimateConfig,
+    params: &PlanetParams,
+    elevations: &[f32],
+    ocean_distance: &[f64],
+    lat: f64,
+    step: f64,
+    wraps: bool,
+) -> Vec<f64> {
+    let width = elevations.len();
+    let lat = lat.abs();
+    let eastward = lat >= config.trade_wind_limit && lat < config.westerlies_limit;
+
+    // Номер клетки после `k` шагов от наветренного края
+    let cell = |k: usize| {
+        let k = k % width;
+        if eastward {
+            k
+        } else {
+            width - 1 - k
+        }
+    };
+
+    // На замкнутой строке воздух выходит из океана, иначе приходит с края карты
+    // настолько влажным, насколько этот край близок к морю
+    let (start, mut humidity) = match (0..width).find(|k| ocean_distance[cell(*k)] == 0.0) {
+        Some(k) if wraps => (k, 1.0),
+        _ => (0, (-ocean_distance[cell(0)] / config.inland_range).exp()),
+    };
+
+    let mut moisture = vec![0.0; width];
+    let mut ceiling = params.sea_level;
+    for k in start..start + width {
+        let index = cell(k);
+        let elevation = elevations[index] as f64;
+
+        if elevation < params.sea_level {
+            humidity = 1.0;
+            ceiling = params.sea_level;
+        } else {
+            humidity *= (-step / config.inland_range).exp();
+        }
+        moisture[index] = humidity;
+
+        if elevation > ceiling {
+            humidity *= (-config.rain_shadow * (elevation - ceiling)).exp();
+            ceiling = elevation;
+        }
+    }
+
+    moisture
+}
+
+/// Расстояние от каждой клетки до ближайшего океана в градусах дуги.
+/// Поиск кратчайших путей по восьми соседям; у клеток океана расстояние 0.0.
+fn ocean_distance(heightmap: &Heightmap, params: &PlanetParams) -> Vec<f64> {
+    let grid = Grid::new(heightmap);
+
+    let mut distance = vec![f64::INFINITY; grid.len()];
+    let mut queue = BinaryHeap::new();
+    for (cell, elevation) in heightmap.data().iter().enumerate() {
+        if (*elevation as f64) < params.sea_level {
+            distance[cell] = 0.0;
+            queue.push((Reverse(OrderedFloat(0.0)), cell));
+        }
+    }
+
+    while let Some((Reverse(OrderedFloat(current)), cell)) = queue.pop() {
+        if current > distance[cell] {
+            continue;
+        }
+
+        for (neighbour, step) in grid.neighbours(cell) {
+            let next = current + step;
+            if next < distance[neighbour] {
+                distance[neighbour] = next;
+                queue.push((Reverse(OrderedFloat(next)), neighbour));
+            }
+        }
+    }
+
+    distance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::world::GeoBounds;
+
+    fn simulate(heightmap: &Heightmap) -> Climate {
+        Climate::simulate(
+            heightmap,
+            &PlanetParams::default(),
+            &ClimateConfig::default(),
+        )
+    }
+
+    /// Карта мира 36x18, высота в каждом пикселе задается `elevation(x, y)`.
+    fn world(elevation: impl Fn(usize, usize) -> f32) -> Heightmap {
+        let data = (0..18)
+            .flat_map(|y| (0..36).map(move |x| (x, y)))
+            .map(|(x, y)| elevation(x, y))
+            .collect();
+        Heightmap::from_raw(36, 18, GeoBounds::WORLD, data)
+    }
+
+    #[test]
+    fn temperature_falls_with_latitude_and_elevation() {
+        // Суша поднимается к востоку
+        let heightmap = world(|x, _| 0.1 + 0.02 * x as f32);
+        let temperature = simulate(&heightmap).temperature().clone();
+
+        for y in 0..18 {
+            for x in 1..36 {
+                assert!(temperature.get(x, y) < temperature.get(x - 1, y));
+            }
+        }
+
+        // Строки 8 и 9 ближе всего к экватору
+        for x in 0..36 {
+            for y in 1..9 {
+                assert!(temperature.get(x, y - 1) < temperature.get(x, y));
+                assert!(temperature.get(x, 17 - (y - 1)) < temperature.get(x, 17 - y));
+            }
+        }
+    }
+
+    #[test]
+    fn ocean_is_saturated() {
+        // Западная половина карты — океан
+        let heightmap = world(|x, _| if x < 18 { -0.5 } else { 0.2 });
+        let moisture = simulate(&heightmap).moisture().clone();
+
+        for y in 0..18 {
+            for x in 0..18 {
+                assert_eq!(moisture.get(x, y), 1.0);
+            }
+            for x in 18..36 {
+                assert!(moisture.get(x, y) < 1.0);
+            }
+        }
+    }
+
+    /// На широтах западных ветров хребет задерживает влагу, пришедшую с запада.
+    #[test]
+    fn ridge_casts_rain_shadow() {
+        let row = |ridge: f32| {
+            let data = (0..2)
+                .flat_map(|_| {
+                    (0..60).map(move |x| match x {
+                        0..=4 => -0.5,
+                        30 => ridge,
+                        _ => 0.05,
+                    })
+                })
+                .collect();
+            let heightmap = Heightmap::from_raw(60, 2, GeoBounds::new(40.0, 50.0, 0.0, 60.0), data);
+            simulate(&heightmap).moisture().clone()
+        };
+
+        let (flat, ridge) = (row(0.05), row(0.5));
+        for y in 0..2 {
+            let (upwind, downwind) = (ridge.get(28, y), ridge.get(32, y));
+            assert!(downwind < 0.5 * upwind, "{} vs {}", downwind, upwind);
+
+            assert_eq!(upwind, flat.get(28, y));
+            assert!(downwind < flat.get(32, y));
+        }
+    }
+
+    #[test]
+    fn climate_is_deterministic() {
+        let heightmap = world(|x, y| ((x * 7 + y * 13) % 11) as f32 / 10.0 - 0.4);
+
+        assert_eq!(simulate(&heightmap), simulate(&heightmap));
+    }
+}
@@ -0,0 +1,252 @@
+//! Модуль текстовых сидов и кодов мира.
+//!
+//! Код мира — короткая строка в base32 (алфавит Крокфорда), по которой можно
+//! восстановить точно такой же мир. Байтовое представление кода:
+//!
+//! | байты | содержимое                                                     |
+//! |-------|----------------------------------------------------------------|
+//! | 1     | версия формата ([`VERSION`])                                   |
+//! | 4     | seed ключ, little-endian                                       |
+//! | 4     | маска параметров, отличающихся от значений по умолчанию        |
+//! | 8 * n | значения этих параметров (`f64`, little-endian) по порядку     |
+//! | 4     | контрольная сумма FNV-1a всех предыдущих байт, little-endian   |
+
+use std::fmt;
+
+use crate::resource::world::PlanetParams;
+
+/// Текущая версия формата кода мира.
+const VERSION: u8 = 1;
+
+/// Алфавит base32 Крокфорда: без букв I, L, O и U, которые легко спутать.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// 32-битный хеш FNV-1a.
+///
+/// Используется для перевода текстового seed ключа в число и для контрольной
+/// суммы кода мира. Алгоритм зафиксирован, поэтому один и тот же текст дает
+/// один и тот же seed ключ на любой платформе и в любой версии игры.
+pub(super) fn fnv1a(bytes: &[u8]) -> u32 {
+    const OFFSET_BASIS: u32 = 0x811c_9dc5;
+    const PRIME: u32 = 0x0100_0193;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u32::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
+/// Кодирование seed ключа и параметров в код мира.
+/// В код попадают только параметры, отличающиеся от значений по умолчанию.
+pub(super) fn encode(seed: u32, params: &PlanetParams) -> String {
+    let defaults = PlanetParams::default().values();
+
+    let mut mask = 0u32;
+    let mut bytes = vec![VERSION];
+    bytes.extend_from_slice(&seed.to_le_bytes());
+
+    let mut values = Vec::new();
+    for (i, (value, default)) in params.values().iter().zip(defaults).enumerate() {
+        if value.to_bits() != default.to_bits() {
+            mask |= 1 << i;
+            values.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    bytes.extend_from_slice(&mask.to_le_bytes());
+    bytes.extend_from_slice(&values);
+    bytes.extend_from_slice(&fnv1a(&bytes).to_le_bytes());
+
+    to_base32(&bytes)
+}
+
+/// Разбор кода мира.
+///
+/// Регистр букв не важен, дефисы и пробелы игнорируются, `O` читается как `0`,
+/// а `I` и `L` как `1`.
+pub(super) fn decode(code: &str) -> Result<(u32, PlanetParams), WorldCodeError> {
+    let bytes = from_base32(code)?;
+
+    // Версия, seed ключ, маска и контрольная сумма.
+    if bytes.len() < 13 {
+        return Err(WorldCodeError::InvalidLength);
+    }
+
+    let (payload, checksum) = bytes.split_at(bytes.len() - 4);
+    if fnv1a(payload).to_le_bytes() != checksum {
+        return Err(WorldCodeError::ChecksumMismatch);
+    }
+
+    if payload[0] != VERSION {
+        return Err(WorldCodeError::UnsupportedVersion(payload[0]));
+    }
+
+    let seed = u32::from_le_bytes([payload[1], payload[2], payload[3], payload[4]]);
+    let mask = u32::from_le_bytes([payload[5], payload[6], payload[7], payload[8]]);
+
+    if mask >> PlanetParams::COUNT != 0 {
+        return Err(WorldCodeError::InvalidLength);
+    }
+
+    let mut values = payload[9..].chunks_exact(8);
+    if values.len() != mask.count_ones() as usize || !values.remainder().is_empty() {
+        return Err(WorldCodeError::InvalidLength);
+    }
+
+    let mut params = PlanetParams::default();
+    for (i, field) in params.values_mut().into_iter().enumerate() {
+        if mask & (1 << i) != 0 {
+            // Количество значений проверено выше.
+            let value = values.next().unwrap();
+            *field = f64::from_le_bytes(value.try_into().unwrap());
+        }
+    }
+
+    Ok((seed, params))
+}
+
+fn to_base32(bytes: &[u8]) -> String {
+    let mut code = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for byte in bytes {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            code.push(ALPHABET[(buffer >> bits) as usize & 31] as char);
+        }
+    }
+
+    if bits > 0 {
+        code.push(ALPHABET[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+
+    code
+}
+
+fn from_base32(code: &str) -> Result<Vec<u8>, WorldCodeError> {
+    let mut bytes = Vec::with_capacity(code.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in code.chars().filter(|c| *c != '-' && !c.is_whitespace()) {
+        let digit = match c.to_ascii_uppercase() {
+            'O' => 0,
+            'I' | 'L' => 1,
+            upper => ALPHABET
+                .iter()
+                .position(|a| *a as char == upper)
+                .ok_or(WorldCodeError::InvalidCharacter(c))? as u32,
+        };
+
+        buffer = (buffer << 5) | digit;
+        bits += 5;
+
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+
+    // Оставшиеся биты — дополнение последнего символа, они обязаны быть нулевыми.
+    if bits >= 5 || buffer & ((1 << bits) - 1) != 0 {
+        return Err(WorldCodeError::InvalidLength);
+    }
+
+    Ok(bytes)
+}
+
+/// Ошибка разбора кода мира.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorldCodeError {
+    /// Символ не входит в алфавит кода.
+    InvalidCharacter(char),
+
+    /// Длина кода не соответствует его содержимому.
+    InvalidLength,
+
+    /// Контрольная сумма не совпала: код поврежден или набран с ошибкой.
+    ChecksumMismatch,
+
+    /// Код создан неизвестной версией формата.
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for WorldCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidCharacter(c) => write!(f, "invalid character {:?} in world code", c),
+            Self::InvalidLength => write!(f, "world code has an invalid length"),
+            Self::ChecksumMismatch => write!(f, "world code checksum mismatch"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported world code version {}", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorldCodeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> PlanetParams {
+        PlanetParams {
+            sea_level: 0.125,
+            mountains_twist: 0.75,
+            ..PlanetParams::default()
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let code = encode(0xdead_beef, &params());
+        assert_eq!(decode(&code), Ok((0xdead_beef, params())));
+
+        let code = encode(7, &PlanetParams::default());
+        assert_eq!(decode(&code), Ok((7, PlanetParams::default())));
+    }
+
+    #[test]
+    fn decode_ignores_case_and_separators() {
+        let code = encode(42, &params());
+        let (head, tail) = code.split_at(code.len() / 2);
+        let formatted = format!("{}-{} ", head.to_lowercase(), tail);
+
+        assert_eq!(decode(&formatted), Ok((42, params())));
+    }
+
+    #[test]
+    fn corrupted_character_is_checksum_mismatch() {
+        let code = encode(42, &params());
+
+        // Последний символ содержит биты дополнения, его порча дает другую ошибку.
+        for i in 0..code.len() - 1 {
+            let mut corrupted = code.clone().into_bytes();
+            corrupted[i] = if corrupted[i] == b'7' { b'8' } else { b'7' };
+            let corrupted = String::from_utf8(corrupted).unwrap();
+
+            assert_eq!(
+                decode(&corrupted),
+                Err(WorldCodeError::ChecksumMismatch),
+                "character {} of {}",
+                i,
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn empty_code_is_invalid_length() {
+        assert_eq!(decode(""), Err(WorldCodeError::InvalidLength));
+        assert_eq!(decode(" - "), Err(WorldCodeError::InvalidLength));
+    }
+
+    #[test]
+    fn unknown_character_is_rejected() {
+        assert_eq!(decode("12U4"), Err(WorldCodeError::InvalidCharacter('U')));
+    }
+}
@@ -0,0 +1,471 @@
+//! Модуль эрозии.
+//!
+//! Шумовой рельеф не знает выветривания: склоны у него одинаковые, а в долинах
+//! нет наносов. Эрозия дорабатывает готовую карту высот в два прохода:
+//! - гидравлическая эрозия: капли дождя падают в случайные точки суши, стекают
+//!   по склону, размывают его и откладывают осадок там, где замедляются, —
+//!   на подъеме, на равнине и у берега моря;
+//! - осыпание (термальная эрозия): склоны круче устойчивого уклона осыпаются
+//!   к подножию.
+//!
+//! Случайность берется только из генератора с seed ключом мира, а клетки
+//! обходятся в одном потоке по порядку, поэтому при одном seed ключе результат
+//! всегда одинаковый.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use strum_macros::{Display, EnumCount, EnumIter, EnumString};
+
+use crate::resource::world::heightmap::Grid;
+use crate::resource::world::shading::wraps_around;
+use crate::resource::world::{Heightmap, PlanetParams};
+
+/// Параметры эрозии.
+///
+/// Расстояния капель измеряются в пикселях, высоты — в планетарных единицах высоты.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ErosionConfig {
+    /// Число капель гидравлической эрозии.
+    pub droplets: usize,
+    /// Наибольшее число шагов одной капли.
+    pub droplet_lifetime: usize,
+    /// Доля прежнего направления капли в новом, от 0.0 до 1.0.
+    pub inertia: f64,
+    /// Сколько осадка уносит капля на единицу перепада высот, скорости и воды.
+    pub sediment_capacity: f64,
+    /// Сколько осадка капля уносит даже по ровному месту.
+    pub min_sediment_capacity: f64,
+    /// Доля недостающего до полной загрузки осадка, которую капля размывает за шаг.
+    pub erosion_rate: f64,
+    /// Доля лишнего осадка, которую капля откладывает за шаг.
+    pub deposition_rate: f64,
+    /// Доля воды, испаряющейся за шаг.
+    pub evaporation: f64,
+    pub gravity: f64,
+    /// Радиус размыва вокруг капли в пикселях.
+    pub erosion_radius: usize,
+    /// Число проходов осыпания.
+    pub thermal_iterations: usize,
+    /// Устойчивый уклон в единицах высоты на градус дуги. Круче склон осыпается.
+    pub talus_slope: f64,
+    /// Доля лишней над устойчивым уклоном высоты, которая осыпается за проход.
+    pub thermal_rate: f64,
+}
+
+impl Default for ErosionConfig {
+    fn default() -> Self {
+        Self {
+            droplets: 200_000,
+            droplet_lifetime: 30,
+            inertia: 0.05,
+            sediment_capacity: 1.0,
+            min_sediment_capacity: 0.001,
+            erosion_rate: 0.1,
+            deposition_rate: 0.3,
+            evaporation: 0.01,
+            gravity: 4.0,
+            erosion_radius: 2,
+            thermal_iterations: 20,
+            talus_slope: 0.25,
+            thermal_rate: 0.5,
+        }
+    }
+}
+
+/// Отладочный слой эрозии.
+///
+/// Название слоя в `snake_case`, например `"deposited"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumCount, EnumIter, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum ErosionLayer {
+    /// Сколько высоты размыто или осыпалось в каждой клетке.
+    Eroded,
+    /// Сколько осадка и осыпи отложено в каждой клетке.
+    Deposited,
+}
+
+/// Карта высот после эрозии вместе с отладочными слоями.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Erosion {
+    heightmap: Heightmap,
+    eroded: Heightmap,
+    deposited: Heightmap,
+}
+
+impl Erosion {
+    /// Эрозия карты высот `heightmap` планеты с параметрами `params`.
+    ///
+    /// Капли падают по генератору с seed ключом `seed`. Чтобы эрозия
+    /// восстанавливалась по коду мира, передается seed ключ мира
+    /// ([`WorldBuilder::seed`](crate::resource::world::WorldBuilder::seed)).
+    pub fn simulate(
+        heightmap: &Heightmap,
+        params: &PlanetParams,
+        seed: u32,
+        config: &ErosionConfig,
+    ) -> Self {
+        let (width, height, bounds) = (heightmap.width(), heightmap.height(), heightmap.bounds());
+
+        let mut terrain = Terrain {
+            grid: Grid::new(heightmap),
+            wraps: wraps_around(heightmap),
+            width,
+            heights: heightmap.data().iter().map(|value| *value as f64).collect(),
+            eroded: vec![0.0; width * height],
+            deposited: vec![0.0; width * height],
+        };
+
+        if width > 1 && height > 1 {
+            let mut rng = StdRng::seed_from_u64(u64::from(seed));
+            let brush = brush(config.erosion_radius);
+
+            // Капли падают равномерно по площади, а не по пикселям карты
+            let (south, north) = (bounds.south.to_radians(), bounds.north.to_radians());
+            for _ in 0..config.droplets {
+                let lat = rng.gen_range(south.sin()..=north.sin()).asin().to_degrees();
+                let y = (bounds.north - lat) / bounds.lat_extent() * height as f64 - 0.5;
+                let x = rng.gen_range(0.0..(width - 1) as f64);
+
+                terrain.droplet(params, config, &brush, x, y.clamp(0.0, (height - 2) as f64));
+            }
+        }
+
+        for _ in 0..config.thermal_iterations {
+            terrain.thermal(params, config);
+        }
+
+        let to_heightmap = |values: Vec<f64>| {
+            Heightmap::from_raw(
+                width,
+                height,
+                bounds,
+                values.into_iter().map(|value| value as f32).collect(),
+            )
+        };
+
+        Self {
+            heightmap: to_heightmap(terrain.heights),
+            eroded: to_heightmap(terrain.eroded),
+            deposited: to_heightmap(terrain.deposited),
+        }
+    }
+
+    /// Высоты после эрозии.
+    pub fn heightmap(&self) -> &Heightmap {
+        &self.heightmap
+    }
+
+    pub fn eroded(&self) -> &Heightmap {
+        &self.eroded
+    }
+
+    pub fn deposited(&self) -> &Heightmap {
+        &self.deposited
+    }
+
+    pub fn layer(&self, layer: ErosionLayer) -> &Heightmap {
+        match layer {
+            ErosionLayer::Eroded => &self.eroded,
+            ErosionLayer::Deposited => &self.deposited,
+        }
+    }
+}
+
+/// Высоты, которые меняет эрозия, и учет размытого и отложенного.
+struct Terrain {
+    grid: Grid,
+    wraps: bool,
+    width: usize,
+    heights: Vec<f64>,
+    eroded: Vec<f64>,
+    deposited: Vec<f64>,
+}
+
+/// Высота и уклон в точке между центрами четырех пикселей.
+struct Sample {
+    height: f64,
+    /// Уклон на пиксель по столбцам и по строкам.
+    gradient: (f64, f64),
+    cells: [usize; 4],
+    weights: [f64; 4],
+}
+
+impl Terrain {
+    /// Путь одной капли из точки (`x`, `y`) в пикселях.
+    fn droplet(
+        &mut self,
+        params: &PlanetParams,
+        config: &ErosionConfig,
+        brush: &[(isize, isize, f64)],
+        mut x: f64,
+        mut y: f64,
+    ) {
+        let (mut direction, mut speed, mut water, mut sediment) = ((0.0, 0.0), 1.0, 1.0, 0.0);
+
+        for _ in 0..config.droplet_lifetime {
+            let current = match self.sample(x, y) {
+                Some(sample) => sample,
+                None => break,
+            };
+
+            // В море осадок оседает у берега, разойдясь по кисти
+            if current.height < params.sea_level {
+                self.settle(current.cells[0], brush, sediment);
+                break;
+            }
+
+            // Направление считается в градусах дуги, а шаг — в пикселях,
+            // которые к полюсам сужаются
+            let (pixel_width, pixel_height) = self.grid.pixel_size(y as usize);
+            let stretch = pixel_width / pixel_height;
+            direction = (
+                direction.0 * config.inertia
+                    - current.gradient.0 / stretch * (1.0 - config.inertia),
+                direction.1 * config.inertia - current.gradient.1 * (1.0 - config.inertia),
+            );
+
+            let step = (direction.0 / stretch, direction.1);
+            let length = step.0.hypot(step.1);
+            if length < f64::EPSILON {
+                break;
+            }
+            direction = (direction.0 / length, direction.1 / length);
+            x += step.0 / length;
+            y += step.1 / length;
+            if self.wraps {
+                x = x.rem_euclid(self.width as f64);
+            }
+
+            let next = match self.sample(x, y) {
+                Some(sample) => sample,
+                None => break,
+            };
+
+            let delta = next.height - current.height;
+            let capacity = (-delta * speed * water * config.sediment_capacity)
+                .max(config.min_sediment_capacity);
+
+            if delta > 0.0 || sediment > capacity {
+                // На подъеме капля засыпает яму позади себя, иначе сбрасывает лишнее
+                let amount = match delta > 0.0 {
+                    true => delta.min(sediment),
+                    false => (sediment - capacity) * config.deposition_rate,
+                };
+                sediment -= amount;
+                self.deposit(&current, amount);
+            } else {
+                // Размыв не глубже перепада, чтобы не выкопать яму
+                let amount = ((capacity - sediment) * config.erosion_rate).min(-delta);
+                sediment += self.erode(current.cells[0], brush, amount);
+            }
+
+            speed = (speed * speed - delta * config.gravity).max(0.0).sqrt();
+            water *= 1.0 - config.evaporation;
+        }
+    }
+
+    /// Билинейная высота и уклон в точке (`x`, `y`) в пикселях.
+    /// `None`, если точка за краем карты.
+    fn sample(&self, x: f64, y: f64) -> Option<Sample> {
+        let (column, row) = (x.floor(), y.floor());
+        let (fx, fy) = (x - column, y - row);
+        let (column, row) = (column as isize, row as isize);
+
+        let cells = [
+            self.grid.cell(column, row)?,
+            self.grid.cell(column + 1, row)?,
+            self.grid.cell(column, row + 1)?,
+            self.grid.cell(column + 1, row + 1)?,
+        ];
+        let [north_west, north_east, south_west, south_east] = cells.map(|cell| self.heights[cell]);
+        let weights = [
+            (1.0 - fx) * (1.0 - fy),
+            fx * (1.0 - fy),
+            (1.0 - fx) * fy,
+            fx * fy,
+        ];
+
+        Some(Sample {
+            height: north_west * weights[0]
+                + north_east * weights[1]
+                + south_west * weights[2]
+                + south_east * weights[3],
+            gradient: (
+                (north_east - north_west) * (1.0 - fy) + (south_east - south_west) * fy,
+                (south_west - north_west) * (1.0 - fx) + (south_east - north_east) * fx,
+            ),
+            cells,
+            weights,
+        })
+    }
+
+    /// Осадок `amount` откладывается в четыре пикселя вокруг точки.
+    fn deposit(&mut self, sample: &Sample, amount: f64) {
+        for (cell, weight) in sample.cells.into_iter().zip(sample.weights) {
+            self.heights[cell] += amount * weight;
+            self.deposited[cell] += amount * weight;
+        }
+    }
+
+    /// Осадок `amount` откладывается вокруг пикселя `center` по кисти `brush`.
+    fn settle(&mut self, center: usize, brush: &[(isize, isize, f64)], amount: f64) {
+        let (x, y) = (
+            (center % self.width) as isize,
+            (center / self.width) as isize,
+        );
+
+        for (dx, dy, weight) in brush {
+            if let Some(cell) = self.grid.cell(x + dx, y + dy) {
+                self.heights[cell] += amount * weight;
+                self.deposited[cell] += amount * weight;
+            }
+        }
+    }
+
+    /// Размыв `amount` вокруг пикселя `center` по кисти `brush`.
+    /// Возвращает, сколько размыто: за краем карты размывать нечего.
+    fn erode(&mut self, center: usize, brush: &[(isize, isize, f64)], amount: f64) -> f64 {
+        let (x, y) = (
+            (center % self.width) as isize,
+            (center / self.width) as isize,
+        );
+
+        let mut total = 0.0;
+        for (dx, dy, weight) in brush {
+            if let Some(cell) = self.grid.cell(x + dx, y + dy) {
+                self.heights[cell] -= amount * weight;
+                self.eroded[cell] += amount * weight;
+                total += amount * weight;
+            }
+        }
+
+        total
+    }
+
+    /// Один проход осыпания. Все клетки осыпаются одновременно,
+    /// поэтому результат не зависит от порядка обхода.
+    fn thermal(&mut self, params: &PlanetParams, config: &ErosionConfig) {
+        let mut change = vec![0.0; self.heights.len()];
+
+        // Осыпается только суша: подводные склоны шельфа задает шумовой граф
+        for cell in (0..self.heights.len()).filter(|cell| self.heights[*cell] >= params.sea_level) {
+            let mut excess = [(0, 0.0); 8];
+            let (mut count, mut total, mut steepest) = (0, 0.0, 0.0f64);
+
+            for (neighbour, distance) in self.grid.neighbours(cell) {
+                let drop =
+                    self.heights[cell] - self.heights[neighbour] - config.talus_slope * distance;
+                if drop > 0.0 {
+                    excess[count] = (neighbour, drop);
+                    count += 1;
+                    total += drop;
+                    steepest = steepest.max(drop);
+                }
+            }
+            if count == 0 {
+                continue;
+            }
+
+            // Осыпается половина лишнего, чтобы склон не перевернулся
+            let moved = config.thermal_rate * steepest / 2.0;
+            change[cell] -= moved;
+            self.eroded[cell] += moved;
+            for (neighbour, drop) in &excess[..count] {
+                change[*neighbour] += moved * drop / total;
+                self.deposited[*neighbour] += moved * drop / total;
+            }
+        }
+
+        for (height, change) in self.heights.iter_mut().zip(change) {
+            *height += change;
+        }
+    }
+}
+
+/// Смещения и веса кисти размыва радиуса `radius`. Веса убывают от центра
+/// и в сумме дают 1.0.
+fn brush(radius: usize) -> Vec<(isize, isize, f64)> {
+    let radius = radius as isize;
+    let mut brush = Vec::new();
+
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            let distance = ((dx * dx + dy * dy) as f64).sqrt();
+            if distance <= radius as f64 {
+                brush.push((dx, dy, 1.0 - distance / (radius as f64 + 1.0)));
+            }
+        }
+    }
+
+    let total: f64 = brush.iter().map(|(_, _, weight)| weight).sum();
+    for (_, _, weight) in &mut brush {
+        *weight /= total;
+    }
+
+    brush
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::world::WorldBuilder;
+
+    fn heightmap() -> (Heightmap, PlanetParams) {
+        let builder = WorldBuilder::new().set_seed(3);
+        let planet = builder.clone().build().unwrap();
+
+        (planet.render_heightmap(64, 32), *builder.params())
+    }
+
+    fn config() -> ErosionConfig {
+        ErosionConfig {
+            droplets: 5_000,
+            thermal_iterations: 5,
+            ..ErosionConfig::default()
+        }
+    }
+
+    #[test]
+    fn same_seed_same_result() {
+        let (heightmap, params) = heightmap();
+
+        let first = Erosion::simulate(&heightmap, &params, 42, &config());
+        let second = Erosion::simulate(&heightmap, &params, 42, &config());
+        assert_eq!(first, second);
+        assert_ne!(first.heightmap(), &heightmap);
+
+        let other = Erosion::simulate(&heightmap, &params, 43, &config());
+        assert_ne!(first.heightmap(), other.heightmap());
+    }
+
+    #[test]
+    fn height_change_is_deposited_minus_eroded() {
+        let (heightmap, params) = heightmap();
+        let erosion = Erosion::simulate(&heightmap, &params, 42, &config());
+
+        let cells = heightmap.data().iter().zip(erosion.heightmap().data()).zip(
+            erosion
+                .eroded()
+                .data()
+                .iter()
+                .zip(erosion.deposited().data()),
+        );
+        for (i, ((before, after), (eroded, deposited))) in cells.enumerate() {
+            assert!(
+                ((after - before) - (deposited - eroded)).abs() < 1e-5,
+                "cell {}: {} -> {}, eroded {}, deposited {}",
+                i,
+                before,
+                after,
+                eroded,
+                deposited
+            );
+        }
+
+        let total = |map: &Heightmap| map.data().iter().map(|value| *value as f64).sum::<f64>();
+        let change = total(erosion.heightmap()) - total(&heightmap);
+        let balance = total(erosion.deposited()) - total(erosion.eroded());
+        assert!((change - balance).abs() < 1e-3, "{} != {}", change, balance);
+        assert!(total(erosion.eroded()) > 0.0);
+    }
+}
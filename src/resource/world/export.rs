@@ -0,0 +1,428 @@
+//! Модуль экспорта и импорта карты высот.
+//!
+//! Поддерживаются три формата:
+//! - 16-битный PNG в оттенках серого, нормализованный к заданному диапазону высот;
+//! - сырые `f32` little-endian с JSON описанием размеров и границ рядом с файлом;
+//! - ESRI ASCII grid.
+//!
+//! Для каждого формата есть парная функция чтения, поэтому данные можно
+//! сохранять и загружать без потерь (PNG — с точностью до 16-битного квантования).
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::resource::world::{GeoBounds, Heightmap};
+
+/// Значение "нет данных" в ESRI ASCII grid.
+const NODATA_VALUE: f32 = -9999.0;
+
+/// Описание сырого файла высот.
+#[derive(Debug, Serialize, Deserialize)]
+struct RawSidecar {
+    width: usize,
+    height: usize,
+    bounds: GeoBounds,
+    min: f32,
+    max: f32,
+}
+
+impl Heightmap {
+    /// Сохранение в 16-битный PNG в оттенках серого.
+    ///
+    /// Высоты из `range` линейно переводятся в диапазон 0..=65535,
+    /// значения за пределами `range` обрезаются. Если `range` вырожден
+    /// (например, у плоской карты), все пиксели получают уровень 0.
+    pub fn save_png16(&self, path: impl AsRef<Path>, range: RangeInclusive<f32>) -> io::Result<()> {
+        let (low, high) = (*range.start(), *range.end());
+        let file = BufWriter::new(File::create(path)?);
+
+        let mut encoder = png::Encoder::new(file, self.width() as u32, self.height() as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Sixteen);
+
+        let span = high - low;
+        let mut bytes = Vec::with_capacity(self.data().len() * 2);
+        for value in self.data() {
+            let normalized = if span > 0.0 {
+                ((value - low) / span).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let level = (normalized * u16::MAX as f32).round() as u16;
+            bytes.extend_from_slice(&level.to_be_bytes());
+        }
+
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&bytes))
+            .map_err(invalid_data)
+    }
+
+    /// Загрузка 16-битного PNG, сохраненного через [`Heightmap::save_png16`].
+    ///
+    /// PNG не хранит ни границ, ни диапазона высот, поэтому их нужно передать
+    /// те же, что и при сохранении.
+    pub fn load_png16(
+        path: impl AsRef<Path>,
+        bounds: GeoBounds,
+        range: RangeInclusive<f32>,
+    ) -> io::Result<Self> {
+        let (low, high) = (*range.start(), *range.end());
+        let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        let mut reader = decoder.read_info().map_err(invalid_data)?;
+
+        let mut bytes = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut bytes).map_err(invalid_data)?;
+
+        if info.color_type != png::ColorType::Grayscale || info.bit_depth != png::BitDepth::Sixteen
+        {
+            return Err(invalid_data("expected a 16-bit grayscale PNG"));
+        }
+
+        let data = bytes[..info.buffer_size()]
+            .chunks_exact(2)
+            .map(|level| {
+                let level = u16::from_be_bytes([level[0], level[1]]) as f32 / u16::MAX as f32;
+                low + level * (high - low)
+            })
+            .collect();
+
+        Ok(Self::from_raw(
+            info.width as usize,
+            info.height as usize,
+            bounds,
+            data,
+        ))
+    }
+
+    /// Сохранение в сырые `f32` little-endian.
+    ///
+    /// Рядом с файлом записывается JSON описание: размеры, границы и диапазон высот.
+    /// Его имя — имя файла с добавленным `.json`, например `heights.raw.json`.
+    pub fn save_raw(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+
+        let mut file = BufWriter::new(File::create(path)?);
+        for value in self.data() {
+            file.write_all(&value.to_le_bytes())?;
+        }
+        file.flush()?;
+
+        let sidecar = RawSidecar {
+            width: self.width(),
+            height: self.height(),
+            bounds: self.bounds(),
+            min: self.min(),
+            max: self.max(),
+        };
+        let file = BufWriter::new(File::create(sidecar_path(path))?);
+        serde_json::to_writer_pretty(file, &sidecar)?;
+
+        Ok(())
+    }
+
+    /// Загрузка сырых `f32`, сохраненных через [`Heightmap::save_raw`].
+    pub fn load_raw(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+
+        let sidecar: RawSidecar =
+            serde_json::from_reader(BufReader::new(File::open(sidecar_path(path))?))?;
+
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+
+        if bytes.len() != sidecar.width * sidecar.height * 4 {
+            return Err(invalid_data(
+                "raw heightmap size does not match its sidecar",
+            ));
+        }
+
+        let data = bytes
+            .chunks_exact(4)
+            .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+            .collect();
+
+        Ok(Self::from_raw(
+            sidecar.width,
+            sidecar.height,
+            sidecar.bounds,
+            data,
+        ))
+    }
+
+    /// Сохранение в ESRI ASCII grid.
+    ///
+    /// Если ячейки не квадратные, вместо `cellsize` записываются `dx` и `dy`,
+    /// как это делает GDAL. Ячейки `NaN` записываются как `NODATA_value`.
+    pub fn save_ascii_grid(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let bounds = self.bounds();
+        let dx = bounds.lon_extent() / self.width() as f64;
+        let dy = bounds.lat_extent() / self.height() as f64;
+
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "ncols {}", self.width())?;
+        writeln!(file, "nrows {}", self.height())?;
+        writeln!(file, "xllcorner {}", bounds.west)?;
+        writeln!(file, "yllcorner {}", bounds.south)?;
+        if dx == dy {
+            writeln!(file, "cellsize {}", dx)?;
+        } else {
+            writeln!(file, "dx {}", dx)?;
+            writeln!(file, "dy {}", dy)?;
+        }
+        writeln!(file, "NODATA_value {}", NODATA_VALUE)?;
+
+        for row in self.data().chunks(self.width()) {
+            let row: Vec<String> = row
+                .iter()
+                .map(|value| match value.is_nan() {
+                    true => NODATA_VALUE.to_string(),
+                    false => value.to_string(),
+                })
+                .collect();
+            writeln!(file, "{}", row.join(" "))?;
+        }
+
+        file.flush()
+    }
+
+    /// Загрузка ESRI ASCII grid.
+    /// Положение сетки задается углом (`xllcorner`, `yllcorner`) или центром
+    /// нижней левой ячейки (`xllcenter`, `yllcenter`).
+    /// Ячейки со значением `NODATA_value` загружаются как `NaN`.
+    pub fn load_ascii_grid(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+
+        let mut lines = file.lines();
+        let mut ncols = None;
+        let mut nrows = None;
+        let mut xllcorner = None;
+        let mut yllcorner = None;
+        let mut xllcenter = None;
+        let mut yllcenter = None;
+        let mut dx = None;
+        let mut dy = None;
+        let mut nodata = None;
+        let mut data = Vec::new();
+
+        for line in &mut lines {
+            let line = line?;
+            let mut tokens = line.split_whitespace();
+            let (key, value) = match (tokens.next(), tokens.next()) {
+                (Some(key), Some(value)) if key.starts_with(char::is_alphabetic) => (key, value),
+                _ => {
+                    // Заголовок закончился, началась первая строка данных.
+                    parse_row(&line, &mut data)?;
+                    break;
+                }
+            };
+
+            let value: f64 = value.parse().map_err(invalid_data)?;
+            match key.to_ascii_lowercase().as_str() {
+                "ncols" => ncols = Some(value as usize),
+                "nrows" => nrows = Some(value as usize),
+                "xllcorner" => xllcorner = Some(value),
+                "yllcorner" => yllcorner = Some(value),
+                "xllcenter" => xllcenter = Some(value),
+                "yllcenter" => yllcenter = Some(value),
+                "cellsize" => (dx, dy) = (Some(value), Some(value)),
+                "dx" => dx = Some(value),
+                "dy" => dy = Some(value),
+                "nodata_value" => nodata = Some(value as f32),
+                _ => return Err(invalid_data(format!("unknown grid header {}", key))),
+            }
+        }
+
+        for line in lines {
+            parse_row(&line?, &mut data)?;
+        }
+
+        let (ncols, nrows, dx, dy) = match (ncols, nrows, dx, dy) {
+            (Some(ncols), Some(nrows), Some(dx), Some(dy)) => (ncols, nrows, dx, dy),
+            _ => return Err(invalid_data("incomplete ASCII grid header")),
+        };
+
+        let west = xllcorner.or_else(|| xllcenter.map(|x| x - dx / 2.0));
+        let south = yllcorner.or_else(|| yllcenter.map(|y| y - dy / 2.0));
+        let (west, south) = match (west, south) {
+            (Some(west), Some(south)) => (west, south),
+            _ => return Err(invalid_data("incomplete ASCII grid header")),
+        };
+
+        if data.len() != ncols * nrows {
+            return Err(invalid_data("ASCII grid size does not match its header"));
+        }
+
+        if let Some(nodata) = nodata {
+            for value in data.iter_mut().filter(|value| **value == nodata) {
+                *value = f32::NAN;
+            }
+        }
+
+        let bounds = GeoBounds::new(
+            south,
+            south + dy * nrows as f64,
+            west,
+            west + dx * ncols as f64,
+        );
+
+        Ok(Self::from_raw(ncols, nrows, bounds, data))
+    }
+}
+
+fn parse_row(line: &str, data: &mut Vec<f32>) -> io::Result<()> {
+    for value in line.split_whitespace() {
+        data.push(value.parse().map_err(invalid_data)?);
+    }
+
+    Ok(())
+}
+
+/// Путь к JSON описанию сырого файла. Расширение не заменяется, а дописывается,
+/// чтобы описание не совпало с самим файлом, если тот уже `.json`.
+fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".json");
+    PathBuf::from(sidecar)
+}
+
+fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// Путь к временному файлу теста `name`.
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("unistone-{}-{}", std::process::id(), name))
+    }
+
+    fn heightmap() -> Heightmap {
+        let (width, height) = (36, 9);
+        let data = (0..width * height)
+            .map(|i| ((i * 37) % 101) as f32 / 50.0 - 1.0)
+            .collect();
+
+        Heightmap::from_raw(
+            width,
+            height,
+            GeoBounds::new(-45.0, 45.0, -90.0, 90.0),
+            data,
+        )
+    }
+
+    #[test]
+    fn png16_round_trip() {
+        let path = temp_path("round-trip.png");
+        let heightmap = heightmap();
+
+        heightmap.save_png16(&path, -1.0..=1.0).unwrap();
+        let loaded = Heightmap::load_png16(&path, heightmap.bounds(), -1.0..=1.0).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.width(), heightmap.width());
+        assert_eq!(loaded.height(), heightmap.height());
+        assert_eq!(loaded.bounds(), heightmap.bounds());
+        for (loaded, value) in loaded.data().iter().zip(heightmap.data()) {
+            assert!((loaded - value).abs() <= 2.0 / u16::MAX as f32);
+        }
+    }
+
+    #[test]
+    fn png16_flat_map() {
+        let path = temp_path("flat.png");
+        let bounds = GeoBounds::WORLD;
+        let heightmap = Heightmap::from_raw(4, 2, bounds, vec![0.5; 8]);
+
+        heightmap.save_png16(&path, 0.5..=0.5).unwrap();
+        let loaded = Heightmap::load_png16(&path, bounds, 0.5..=0.5).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, heightmap);
+    }
+
+    #[test]
+    fn raw_round_trip() {
+        let path = temp_path("round-trip.raw");
+        let heightmap = heightmap();
+
+        heightmap.save_raw(&path).unwrap();
+        let loaded = Heightmap::load_raw(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(sidecar_path(&path)).unwrap();
+
+        assert_eq!(loaded, heightmap);
+    }
+
+    #[test]
+    fn ascii_grid_round_trip() {
+        let path = temp_path("round-trip.asc");
+        let heightmap = heightmap();
+
+        heightmap.save_ascii_grid(&path).unwrap();
+        let loaded = Heightmap::load_ascii_grid(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, heightmap);
+    }
+
+    #[test]
+    fn raw_json_extension_keeps_data() {
+        let path = temp_path("heights.json");
+        let heightmap = heightmap();
+
+        heightmap.save_raw(&path).unwrap();
+        let loaded = Heightmap::load_raw(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(sidecar_path(&path)).unwrap();
+
+        assert_eq!(sidecar_path(&path), temp_path("heights.json.json"));
+        assert_eq!(loaded, heightmap);
+    }
+
+    #[test]
+    fn ascii_grid_nodata_round_trip() {
+        let path = temp_path("nodata.asc");
+        let mut data = heightmap().data().to_vec();
+        data[5] = f32::NAN;
+        let heightmap = Heightmap::from_raw(36, 9, heightmap().bounds(), data);
+
+        heightmap.save_ascii_grid(&path).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let loaded = Heightmap::load_ascii_grid(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(!text.contains("NaN"));
+        assert!(loaded.data()[5].is_nan());
+        for (i, (loaded, value)) in loaded.data().iter().zip(heightmap.data()).enumerate() {
+            if i != 5 {
+                assert_eq!(loaded, value);
+            }
+        }
+    }
+
+    #[test]
+    fn ascii_grid_cell_centers() {
+        let path = temp_path("centers.asc");
+        let grid = "ncols 2\nnrows 2\nxllcenter 5\nyllcenter -5\ncellsize 10\n\
+                    NODATA_value -9999\n1 2\n3 -9999\n";
+        fs::write(&path, grid).unwrap();
+        let loaded = Heightmap::load_ascii_grid(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.bounds(), GeoBounds::new(-10.0, 10.0, 0.0, 20.0));
+        assert_eq!(&loaded.data()[..3], &[1.0, 2.0, 3.0]);
+        assert!(loaded.data()[3].is_nan());
+    }
+}
@@ -0,0 +1,213 @@
+//! Модуль выражений над параметрами планеты.
+//!
+//! Числовые параметры узлов шумового графа задаются числом или строкой с
+//! выражением, например `"continent_frequency * 4.34375"`. Поддерживаются числа,
+//! имена полей [`PlanetParams`], `continent_height_scale`, скобки, унарный минус
+//! и операции `+ - * /` с обычными приоритетами. Выражения вычисляются в том же
+//! порядке, что и такие же выражения в Rust, поэтому результат совпадает до бита.
+
+use serde::Deserialize;
+
+use crate::resource::world::PlanetParams;
+
+/// Выражение над параметрами планеты.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "RawExpr")]
+pub(super) enum Expr {
+    Number(f64),
+    Param(usize),
+    ContinentHeightScale,
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, Op, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// Значение параметра в документе: число или текст выражения.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawExpr {
+    Number(f64),
+    Text(String),
+}
+
+impl TryFrom<RawExpr> for Expr {
+    type Error = String;
+
+    fn try_from(raw: RawExpr) -> Result<Self, Self::Error> {
+        match raw {
+            RawExpr::Number(value) => Ok(Self::Number(value)),
+            RawExpr::Text(text) => Self::parse(&text),
+        }
+    }
+}
+
+impl Expr {
+    /// Разбор выражения из текста.
+    pub(super) fn parse(text: &str) -> Result<Self, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, pos: 0 };
+
+        let expr = parser.expr()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected {:?} in expression {:?}", token, text)),
+        }
+    }
+
+    /// Значение выражения для планеты с параметрами `params`.
+    pub(super) fn eval(&self, params: &PlanetParams) -> f64 {
+        match self {
+            Self::Number(value) => *value,
+            Self::Param(index) => params.values()[*index],
+            Self::ContinentHeightScale => params.continent_height_scale(),
+            Self::Neg(expr) => -expr.eval(params),
+            Self::Binary(lhs, op, rhs) => {
+                let (lhs, rhs) = (lhs.eval(params), rhs.eval(params));
+                match op {
+                    Op::Add => lhs + rhs,
+                    Op::Sub => lhs - rhs,
+                    Op::Mul => lhs * rhs,
+                    Op::Div => lhs / rhs,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(char),
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '+' | '-' | '*' | '/' => tokens.push(Token::Op(c)),
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut end = start + 1;
+                while let Some((i, c)) = chars.peek().copied() {
+                    let exponent_sign = (c == '+' || c == '-')
+                        && matches!(text[..i].chars().last(), Some('e' | 'E'));
+                    if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || exponent_sign {
+                        end = i + 1;
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+
+                let number = &text[start..end];
+                let value = number
+                    .parse()
+                    .map_err(|_| format!("invalid number {:?} in expression {:?}", number, text))?;
+                tokens.push(Token::Number(value));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut end = start + 1;
+                while let Some((i, c)) = chars.peek().copied() {
+                    if c.is_ascii_alphanumeric() || c == '_' {
+                        end = i + 1;
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+
+                tokens.push(Token::Ident(text[start..end].to_string()));
+            }
+            c => return Err(format!("unexpected {:?} in expression {:?}", c, text)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn next_op(&mut self, ops: &[char]) -> Option<Op> {
+        let op = match self.tokens.get(self.pos) {
+            Some(Token::Op(c)) if ops.contains(c) => *c,
+            _ => return None,
+        };
+        self.pos += 1;
+
+        Some(match op {
+            '+' => Op::Add,
+            '-' => Op::Sub,
+            '*' => Op::Mul,
+            _ => Op::Div,
+        })
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.term()?;
+        while let Some(op) = self.next_op(&['+', '-']) {
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(self.term()?));
+        }
+
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        while let Some(op) = self.next_op(&['*', '/']) {
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(self.unary()?));
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.next_op(&['-']).is_some() {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Ident(name)) if name == "continent_height_scale" => {
+                Ok(Expr::ContinentHeightScale)
+            }
+            Some(Token::Ident(name)) => PlanetParams::NAMES
+                .iter()
+                .position(|param| *param == name)
+                .map(Expr::Param)
+                .ok_or_else(|| format!("unknown planet parameter {:?}", name)),
+            Some(Token::Open) => {
+                let expr = self.expr()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err("expected `)` in expression".to_string()),
+                }
+            }
+            Some(token) => Err(format!("unexpected {:?} in expression", token)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
@@ -0,0 +1,734 @@
+//! Модуль описания шумового графа.
+//!
+//! Шумовой граф планеты можно описать RON документом из именованных узлов и
+//! собрать во время работы в то же дерево модулей `noise`, что строит
+//! `continent.rs`. Так дизайнеры могут экспериментировать с графом без
+//! перекомпиляции. Граф текущей планеты поставляется как документ по умолчанию
+//! (`assets/worldgen/planet.ron`), формат описан в его заголовке.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use noise::{
+    Add, Billow, Blend, Cache, Clamp, Constant, Curve, Exponent, Fbm, Max, Min, MultiFractal,
+    Multiply, NoiseFn, RidgedMulti, ScaleBias, Seedable, Select, Terrace, Turbulence, Worley,
+};
+use ron::extensions::Extensions;
+use serde::Deserialize;
+use strum::{EnumCount, IntoEnumIterator};
+
+use crate::resource::world::expr::Expr;
+use crate::resource::world::planet::NoiseGraph;
+use crate::resource::world::{PlanetParams, TerrainLayer};
+
+/// Документ шумового графа по умолчанию.
+const DEFAULT_DOCUMENT: &str = include_str!("../../../assets/worldgen/planet.ron");
+
+/// Описание шумового графа планеты.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct GraphDocument {
+    nodes: BTreeMap<String, Module>,
+    layers: BTreeMap<String, String>,
+}
+
+/// Модуль библиотеки `noise` с параметрами.
+/// Необязательные параметры по умолчанию берутся из самой библиотеки.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+enum Module {
+    Fbm {
+        #[serde(default)]
+        seed: u32,
+        frequency: Option<Expr>,
+        lacunarity: Option<Expr>,
+        persistence: Option<Expr>,
+        octaves: Option<usize>,
+    },
+    Billow {
+        #[serde(default)]
+        seed: u32,
+        frequency: Option<Expr>,
+        lacunarity: Option<Expr>,
+        persistence: Option<Expr>,
+        octaves: Option<usize>,
+    },
+    RidgedMulti {
+        #[serde(default)]
+        seed: u32,
+        frequency: Option<Expr>,
+        lacunarity: Option<Expr>,
+        persistence: Option<Expr>,
+        octaves: Option<usize>,
+    },
+    Worley {
+        #[serde(default)]
+        seed: u32,
+        frequency: Option<Expr>,
+    },
+    Constant {
+        value: Expr,
+    },
+    Cache {
+        source: String,
+    },
+    Turbulence {
+        source: String,
+        #[serde(default)]
+        seed: u32,
+        frequency: Option<Expr>,
+        power: Option<Expr>,
+        roughness: Option<usize>,
+    },
+    ScaleBias {
+        source: String,
+        scale: Option<Expr>,
+        bias: Option<Expr>,
+    },
+    Clamp {
+        source: String,
+        lower: Expr,
+        upper: Expr,
+    },
+    Exponent {
+        source: String,
+        exponent: Expr,
+    },
+    Curve {
+        source: String,
+        points: Vec<(Expr, Expr)>,
+    },
+    Terrace {
+        source: String,
+        points: Vec<Expr>,
+        #[serde(default)]
+        invert: bool,
+    },
+    Add {
+        source1: String,
+        source2: String,
+    },
+    Max {
+        source1: String,
+        source2: String,
+    },
+    Min {
+        source1: String,
+        source2: String,
+    },
+    Multiply {
+        source1: String,
+        source2: String,
+    },
+    Blend {
+        source1: String,
+        source2: String,
+        control: String,
+    },
+    Select {
+        source1: String,
+        source2: String,
+        control: String,
+        lower: Expr,
+        upper: Expr,
+        falloff: Option<Expr>,
+    },
+}
+
+impl Module {
+    /// Имена узлов, от которых зависит модуль.
+    fn sources(&self) -> Vec<&str> {
+        match self {
+            Self::Fbm { .. }
+            | Self::Billow { .. }
+            | Self::RidgedMulti { .. }
+            | Self::Worley { .. }
+            | Self::Constant { .. } => Vec::new(),
+            Self::Cache { source }
+            | Self::Turbulence { source, .. }
+            | Self::ScaleBias { source, .. }
+            | Self::Clamp { source, .. }
+            | Self::Exponent { source, .. }
+            | Self::Curve { source, .. }
+            | Self::Terrace { source, .. } => vec![source],
+            Self::Add { source1, source2 }
+            | Self::Max { source1, source2 }
+            | Self::Min { source1, source2 }
+            | Self::Multiply { source1, source2 } => vec![source1, source2],
+            Self::Blend {
+                source1,
+                source2,
+                control,
+            }
+            | Self::Select {
+                source1,
+                source2,
+                control,
+                ..
+            } => vec![source1, source2, control],
+        }
+    }
+
+    /// Наименьшее число контрольных точек модуля и число различных точек после
+    /// вычисления выражений для `params`.
+    /// `Curve` нужны минимум 4 точки, `Terrace` — 2, иначе `noise` паникует при
+    /// сэмплировании. Совпадающие точки `noise` отбрасывает, поэтому они не считаются.
+    fn control_points(&self, params: &PlanetParams) -> Option<(usize, usize)> {
+        match self {
+            Self::Curve { points, .. } => Some((
+                4,
+                distinct(points.iter().map(|(input, _)| input.eval(params))),
+            )),
+            Self::Terrace { points, .. } => {
+                Some((2, distinct(points.iter().map(|point| point.eval(params)))))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Число различных значений по тому же правилу, по которому `Curve` и `Terrace`
+/// отбрасывают повторные контрольные точки.
+fn distinct(values: impl Iterator<Item = f64>) -> usize {
+    let mut unique: Vec<f64> = Vec::new();
+    for value in values {
+        if !unique.iter().any(|x| (x - value).abs() < f64::EPSILON) {
+            unique.push(value);
+        }
+    }
+
+    unique.len()
+}
+
+impl GraphDocument {
+    /// Разбор документа из текста RON.
+    ///
+    /// Проверяет, что все источники существуют, в графе нет циклов, у кривых и
+    /// террас при параметрах по умолчанию достаточно контрольных точек, а слои
+    /// ссылаются на существующие узлы.
+    pub fn from_ron(text: &str) -> Result<Self, GraphError> {
+        let document: Self = ron::Options::default()
+            .with_default_extension(Extensions::IMPLICIT_SOME)
+            .from_str(text)
+            .map_err(GraphError::Parse)?;
+
+        document.validate()?;
+        Ok(document)
+    }
+
+    /// Загрузка документа из файла.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, GraphError> {
+        Self::from_ron(&fs::read_to_string(path).map_err(GraphError::Io)?)
+    }
+
+    fn validate(&self) -> Result<(), GraphError> {
+        for (name, module) in &self.nodes {
+            for source in module.sources() {
+                if !self.nodes.contains_key(source) {
+                    return Err(GraphError::UnknownSource {
+                        node: name.clone(),
+                        source: source.to_string(),
+                    });
+                }
+            }
+        }
+
+        self.check_points(&PlanetParams::default())?;
+
+        for (layer, node) in &self.layers {
+            if layer.parse::<TerrainLayer>().is_err() {
+                return Err(GraphError::UnknownLayer(layer.clone()));
+            }
+            if !self.nodes.contains_key(node) {
+                return Err(GraphError::UnknownSource {
+                    node: layer.clone(),
+                    source: node.clone(),
+                });
+            }
+        }
+
+        if !self
+            .layers
+            .contains_key(&TerrainLayer::Elevation.to_string())
+        {
+            return Err(GraphError::MissingElevation);
+        }
+
+        // Поиск циклов обходом в глубину: 1 — узел в обработке, 2 — обработан.
+        let mut state = HashMap::new();
+        for name in self.nodes.keys() {
+            self.check_cycles(name, &mut state)?;
+        }
+
+        Ok(())
+    }
+
+    /// Проверка, что при параметрах `params` у кривых и террас достаточно
+    /// различных контрольных точек.
+    pub(super) fn check_points(&self, params: &PlanetParams) -> Result<(), GraphError> {
+        for (name, module) in &self.nodes {
+            if let Some((required, points)) = module.control_points(params) {
+                if points < required {
+                    return Err(GraphError::TooFewPoints {
+                        node: name.clone(),
+                        required,
+                        points,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_cycles<'a>(
+        &'a self,
+        name: &'a str,
+        state: &mut HashMap<&'a str, u8>,
+    ) -> Result<(), GraphError> {
+        match state.get(name) {
+            Some(2) => return Ok(()),
+            Some(_) => return Err(GraphError::Cycle(name.to_string())),
+            None => {}
+        }
+
+        state.insert(name, 1);
+        for source in self.nodes[name].sources() {
+            self.check_cycles(source, state)?;
+        }
+        state.insert(name, 2);
+
+        Ok(())
+    }
+
+    /// Сборка графа в `graph`.
+    ///
+    /// Собираются только узлы, от которых зависят слои. Возвращаются модули всех
+    /// слоев [`TerrainLayer`] в порядке их объявления, отсутствующие в документе
+    /// слои указывают на итоговую высоту.
+    ///
+    /// # Safety
+    ///
+    /// Возвращаемые модули размещены в `graph` и не должны его пережить,
+    /// как и ссылки из [`NoiseGraph::add`].
+    pub(super) unsafe fn build(
+        &self,
+        graph: &mut NoiseGraph,
+        params: &PlanetParams,
+        seed: u32,
+    ) -> [&'static dyn NoiseFn<[f64; 3]>; TerrainLayer::COUNT] {
+        let mut builder = Builder {
+            document: self,
+            graph,
+            params,
+            seed,
+            built: HashMap::new(),
+        };
+
+        let elevation = builder.node(&self.layers[&TerrainLayer::Elevation.to_string()]);
+        let mut layers = [elevation; TerrainLayer::COUNT];
+        for layer in TerrainLayer::iter() {
+            if let Some(node) = self.layers.get(&layer.to_string()) {
+                layers[layer as usize] = builder.node(node);
+            }
+        }
+
+        layers
+    }
+}
+
+impl Default for GraphDocument {
+    /// Граф текущей планеты.
+    fn default() -> Self {
+        Self::from_ron(DEFAULT_DOCUMENT).expect("default noise graph document is invalid")
+    }
+}
+
+/// Ссылка на уже собранный узел графа.
+///
+/// `Cache` и `Turbulence` принимают источник по значению и требуют, чтобы он
+/// реализовывал `NoiseFn`, а для ссылки на `dyn NoiseFn` такой реализации нет.
+#[derive(Clone, Copy)]
+struct Node(&'static dyn NoiseFn<[f64; 3]>);
+
+impl NoiseFn<[f64; 3]> for Node {
+    fn get(&self, point: [f64; 3]) -> f64 {
+        self.0.get(point)
+    }
+}
+
+struct Builder<'a> {
+    document: &'a GraphDocument,
+    graph: &'a mut NoiseGraph,
+    params: &'a PlanetParams,
+    seed: u32,
+    built: HashMap<&'a str, &'static dyn NoiseFn<[f64; 3]>>,
+}
+
+/// Методы, размещающие модули, требуют того же, что и [`GraphDocument::build`].
+impl<'a> Builder<'a> {
+    unsafe fn node(&mut self, name: &'a str) -> &'static dyn NoiseFn<[f64; 3]> {
+        if let Some(node) = self.built.get(name) {
+            return *node;
+        }
+
+        let document = self.document;
+        let node = self.module(&document.nodes[name]);
+        self.built.insert(name, node);
+        node
+    }
+
+    fn eval(&self, expr: &Expr) -> f64 {
+        expr.eval(self.params)
+    }
+
+    unsafe fn module(&mut self, module: &'a Module) -> &'static dyn NoiseFn<[f64; 3]> {
+        match module {
+            Module::Fbm {
+                seed,
+                frequency,
+                lacunarity,
+                persistence,
+                octaves,
+            } => {
+                let fbm = Fbm::new().set_seed(self.seed.wrapping_add(*seed));
+                let fbm = self.fractal(fbm, frequency, lacunarity, persistence, octaves);
+                self.graph.add(fbm)
+            }
+            Module::Billow {
+                seed,
+                frequency,
+                lacunarity,
+                persistence,
+                octaves,
+            } => {
+                let billow = Billow::new().set_seed(self.seed.wrapping_add(*seed));
+                let billow = self.fractal(billow, frequency, lacunarity, persistence, octaves);
+                self.graph.add(billow)
+            }
+            Module::RidgedMulti {
+                seed,
+                frequency,
+                lacunarity,
+                persistence,
+                octaves,
+            } => {
+                let ridged = RidgedMulti::new().set_seed(self.seed.wrapping_add(*seed));
+                let ridged = self.fractal(ridged, frequency, lacunarity, persistence, octaves);
+                self.graph.add(ridged)
+            }
+            Module::Worley { seed, frequency } => {
+                let mut worley = Worley::new().set_seed(self.seed.wrapping_add(*seed));
+                if let Some(frequency) = frequency {
+                    worley = worley.set_frequency(self.eval(frequency));
+                }
+                self.graph.add(worley)
+            }
+            Module::Constant { value } => self.graph.add(Constant::new(self.eval(value))),
+            Module::Cache { source } => {
+                let source = Node(self.node(source));
+                self.graph.add(Cache::new(source))
+            }
+            Module::Turbulence {
+                source,
+                seed,
+                frequency,
+                power,
+                roughness,
+            } => {
+                let source = Node(self.node(source));
+                let mut turbulence =
+                    Turbulence::new(source).set_seed(self.seed.wrapping_add(*seed));
+                if let Some(frequency) = frequency {
+                    turbulence = turbulence.set_frequency(self.eval(frequency));
+                }
+                if let Some(power) = power {
+                    turbulence = turbulence.set_power(self.eval(power));
+                }
+                if let Some(roughness) = roughness {
+                    turbulence = turbulence.set_roughness(*roughness);
+                }
+                self.graph.add(turbulence)
+            }
+            Module::ScaleBias {
+                source,
+                scale,
+                bias,
+            } => {
+                let mut scale_bias = ScaleBias::new(self.node(source));
+                if let Some(scale) = scale {
+                    scale_bias = scale_bias.set_scale(self.eval(scale));
+                }
+                if let Some(bias) = bias {
+                    scale_bias = scale_bias.set_bias(self.eval(bias));
+                }
+                self.graph.add(scale_bias)
+            }
+            Module::Clamp {
+                source,
+                lower,
+                upper,
+            } => {
+                let clamp =
+                    Clamp::new(self.node(source)).set_bounds(self.eval(lower), self.eval(upper));
+                self.graph.add(clamp)
+            }
+            Module::Exponent { source, exponent } => {
+                let exponent = Exponent::new(self.node(source)).set_exponent(self.eval(exponent));
+                self.graph.add(exponent)
+            }
+            Module::Curve { source, points } => {
+                let mut curve = Curve::new(self.node(source));
+                for (input, output) in points {
+                    curve = curve.add_control_point(self.eval(input), self.eval(output));
+                }
+                self.graph.add(curve)
+            }
+            Module::Terrace {
+                source,
+                points,
+                invert,
+            } => {
+                let mut terrace = Terrace::new(self.node(source)).invert_terraces(*invert);
+                for point in points {
+                    terrace = terrace.add_control_point(self.eval(point));
+                }
+                self.graph.add(terrace)
+            }
+            Module::Add { source1, source2 } => {
+                let add = Add::new(self.node(source1), self.node(source2));
+                self.graph.add(add)
+            }
+            Module::Max { source1, source2 } => {
+                let max = Max::new(self.node(source1), self.node(source2));
+                self.graph.add(max)
+            }
+            Module::Min { source1, source2 } => {
+                let min = Min::new(self.node(source1), self.node(source2));
+                self.graph.add(min)
+            }
+            Module::Multiply { source1, source2 } => {
+                let multiply = Multiply::new(self.node(source1), self.node(source2));
+                self.graph.add(multiply)
+            }
+            Module::Blend {
+                source1,
+                source2,
+                control,
+            } => {
+                let blend = Blend::new(self.node(source1), self.node(source2), self.node(control));
+                self.graph.add(blend)
+            }
+            Module::Select {
+                source1,
+                source2,
+                control,
+                lower,
+                upper,
+                falloff,
+            } => {
+                let mut select =
+                    Select::new(self.node(source1), self.node(source2), self.node(control))
+                        .set_bounds(self.eval(lower), self.eval(upper));
+                if let Some(falloff) = falloff {
+                    select = select.set_falloff(self.eval(falloff));
+                }
+                self.graph.add(select)
+            }
+        }
+    }
+
+    /// Настройка фрактального шума. Параметры, не указанные в документе,
+    /// остаются значениями библиотеки по умолчанию.
+    fn fractal<T: MultiFractal>(
+        &self,
+        mut fractal: T,
+        frequency: &Option<Expr>,
+        lacunarity: &Option<Expr>,
+        persistence: &Option<Expr>,
+        octaves: &Option<usize>,
+    ) -> T {
+        if let Some(frequency) = frequency {
+            fractal = fractal.set_frequency(self.eval(frequency));
+        }
+        if let Some(lacunarity) = lacunarity {
+            fractal = fractal.set_lacunarity(self.eval(lacunarity));
+        }
+        if let Some(persistence) = persistence {
+            fractal = fractal.set_persistence(self.eval(persistence));
+        }
+        if let Some(octaves) = octaves {
+            fractal = fractal.set_octaves(*octaves);
+        }
+
+        fractal
+    }
+}
+
+/// Ошибка загрузки документа шумового графа.
+#[derive(Debug)]
+pub enum GraphError {
+    /// Файл документа не удалось прочитать.
+    Io(io::Error),
+
+    /// Документ не является корректным RON описанием графа.
+    Parse(ron::Error),
+
+    /// Узел или слой ссылается на несуществующий узел.
+    UnknownSource { node: String, source: String },
+
+    /// Узел зависит сам от себя.
+    Cycle(String),
+
+    /// У кривой или террасы меньше различных контрольных точек, чем нужно модулю.
+    TooFewPoints {
+        node: String,
+        required: usize,
+        points: usize,
+    },
+
+    /// Неизвестное имя слоя.
+    UnknownLayer(String),
+
+    /// В документе нет слоя `elevation`.
+    MissingElevation,
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "failed to read noise graph: {}", error),
+            Self::Parse(error) => write!(f, "invalid noise graph: {}", error),
+            Self::UnknownSource { node, source } => {
+                write!(f, "{:?} refers to unknown node {:?}", node, source)
+            }
+            Self::Cycle(node) => write!(f, "noise graph node {:?} depends on itself", node),
+            Self::TooFewPoints {
+                node,
+                required,
+                points,
+            } => write!(
+                f,
+                "noise graph node {:?} has {} control points, at least {} required",
+                node, points, required
+            ),
+            Self::UnknownLayer(layer) => write!(f, "unknown terrain layer {:?}", layer),
+            Self::MissingElevation => write!(f, "noise graph has no elevation layer"),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Parse(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(points: &str) -> String {
+        format!(
+            r#"(
+                nodes: {{
+                    "fbm": Fbm(seed: 0),
+                    "curve": Curve(source: "fbm", points: [{}]),
+                    "terrace": Terrace(source: "fbm", points: [-1.0, 1.0]),
+                }},
+                layers: {{ "elevation": "curve" }},
+            )"#,
+            points
+        )
+    }
+
+    #[test]
+    fn default_document_is_valid() {
+        GraphDocument::default();
+    }
+
+    #[test]
+    fn curve_needs_four_points() {
+        let points = "(-1.0, -1.0), (0.0, 0.0), (1.0, 1.0)";
+        match GraphDocument::from_ron(&document(points)) {
+            Err(GraphError::TooFewPoints {
+                node,
+                required: 4,
+                points: 3,
+            }) => assert_eq!(node, "curve"),
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let points = "(-1.0, -1.0), (0.0, 0.0), (0.5, 0.5), (1.0, 1.0)";
+        assert!(GraphDocument::from_ron(&document(points)).is_ok());
+    }
+
+    #[test]
+    fn terrace_needs_two_points() {
+        let text = document("(-1.0, -1.0), (0.0, 0.0), (0.5, 0.5), (1.0, 1.0)")
+            .replace("[-1.0, 1.0]", "[0.0]");
+
+        match GraphDocument::from_ron(&text) {
+            Err(GraphError::TooFewPoints {
+                node,
+                required: 2,
+                points: 1,
+            }) => assert_eq!(node, "terrace"),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    /// `noise` отбрасывает совпадающие точки, поэтому считаются только различные.
+    #[test]
+    fn duplicate_points_are_not_counted() {
+        let points = "(-1.0, -1.0), (0.0, 0.0), (\"0.5 * 2.0 - 1.0\", 0.5), (1.0, 1.0)";
+        match GraphDocument::from_ron(&document(points)) {
+            Err(GraphError::TooFewPoints {
+                node,
+                required: 4,
+                points: 3,
+            }) => assert_eq!(node, "curve"),
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let text = document("(-1.0, -1.0), (0.0, 0.0), (0.5, 0.5), (1.0, 1.0)")
+            .replace("[-1.0, 1.0]", "[0.25, 0.25]");
+        match GraphDocument::from_ron(&text) {
+            Err(GraphError::TooFewPoints {
+                node,
+                required: 2,
+                points: 1,
+            }) => assert_eq!(node, "terrace"),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    /// Точки, совпадающие только при заданных параметрах, обнаруживаются при сборке мира.
+    #[test]
+    fn points_are_checked_with_world_params() {
+        let points = "(-1.0, -1.0), (0.0, 0.0), (\"sea_level + 0.5\", 0.5), (1.0, 1.0)";
+        let document = GraphDocument::from_ron(&document(points)).unwrap();
+
+        let params = PlanetParams {
+            sea_level: 0.5,
+            ..PlanetParams::default()
+        };
+        let result = crate::resource::world::WorldBuilder::new()
+            .set_params(params)
+            .set_graph(document)
+            .build();
+
+        assert!(matches!(
+            result,
+            Err(crate::resource::world::WorldParamError::TooFewGraphPoints {
+                required: 4,
+                points: 3,
+            })
+        ));
+    }
+}
@@ -0,0 +1,298 @@
+//! Модуль карты высот.
+//!
+//! Карта высот — равнопромежуточная (equirectangular) сетка высот планеты,
+//! на которой строятся изображения карт, меши и статистика.
+
+use serde::{Deserialize, Serialize};
+
+/// Границы участка поверхности планеты в градусах.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoBounds {
+    /// Южная граница, широта от -90 до 90.
+    pub south: f64,
+    /// Северная граница, широта от -90 до 90.
+    pub north: f64,
+    /// Западная граница, долгота.
+    pub west: f64,
+    /// Восточная граница, долгота.
+    pub east: f64,
+}
+
+impl GeoBounds {
+    /// Вся поверхность планеты.
+    pub const WORLD: Self = Self {
+        south: -90.0,
+        north: 90.0,
+        west: -180.0,
+        east: 180.0,
+    };
+
+    pub fn new(south: f64, north: f64, west: f64, east: f64) -> Self {
+        Self {
+            south,
+            north,
+            west,
+            east,
+        }
+    }
+
+    /// Протяженность участка по широте в градусах.
+    pub fn lat_extent(&self) -> f64 {
+        self.north - self.south
+    }
+
+    /// Протяженность участка по долготе в градусах.
+    pub fn lon_extent(&self) -> f64 {
+        self.east - self.west
+    }
+}
+
+impl Default for GeoBounds {
+    fn default() -> Self {
+        Self::WORLD
+    }
+}
+
+/// Карта высот планеты.
+///
+/// Строки идут с севера на юг, столбцы — с запада на восток. Каждый пиксель
+/// хранит высоту в центре своей ячейки в планетарных единицах высоты.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heightmap {
+    width: usize,
+    height: usize,
+    bounds: GeoBounds,
+    data: Vec<f32>,
+    min: f32,
+    max: f32,
+}
+
+impl Heightmap {
+    /// Создание карты высот из готовых данных.
+    ///
+    /// Длина `data` должна быть равна `width * height`.
+    pub fn from_raw(width: usize, height: usize, bounds: GeoBounds, data: Vec<f32>) -> Self {
+        assert_eq!(
+            data.len(),
+            width * height,
+            "heightmap data does not match its dimensions"
+        );
+
+        let (min, max) = data
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| {
+                (min.min(*value), max.max(*value))
+            });
+
+        Self {
+            width,
+            height,
+            bounds,
+            data,
+            min,
+            max,
+        }
+    }
+
+    /// Заполнение карты высот значениями `sample(lat, lon)` в центрах пикселей.
+    /// Повторяет обход `NoiseMapBuilderSphere` из libnoise.
+    pub(super) fn sample(
+        width: usize,
+        height: usize,
+        bounds: GeoBounds,
+        mut sample: impl FnMut(f64, f64) -> f64,
+    ) -> Self {
+        let mut data = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let (lat, lon) = pixel_lat_lon(width, height, &bounds, x, y);
+                data.push(sample(lat, lon) as f32);
+            }
+        }
+
+        Self::from_raw(width, height, bounds, data)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn bounds(&self) -> GeoBounds {
+        self.bounds
+    }
+
+    /// Минимальная высота на карте.
+    pub fn min(&self) -> f32 {
+        self.min
+    }
+
+    /// Максимальная высота на карте.
+    pub fn max(&self) -> f32 {
+        self.max
+    }
+
+    /// Высоты построчно, с севера на юг.
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+
+    /// Высота в пикселе (`x`, `y`).
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.data[y * self.width + x]
+    }
+
+    /// Широта и долгота центра пикселя (`x`, `y`) в градусах.
+    pub fn lat_lon(&self, x: usize, y: usize) -> (f64, f64) {
+        pixel_lat_lon(self.width, self.height, &self.bounds, x, y)
+    }
+
+    /// Пиксель, в который попадает точка (`lat`, `lon`).
+    /// `None`, если точка вне границ карты.
+    pub fn pixel_at(&self, lat: f64, lon: f64) -> Option<(usize, usize)> {
+        let bounds = &self.bounds;
+        let east = (lon - bounds.west).rem_euclid(360.0);
+        if east > bounds.lon_extent() || !(bounds.south..=bounds.north).contains(&lat) {
+            return None;
+        }
+
+        // Точки на восточной и южной границах относятся к крайним пикселям
+        let x = (east / bounds.lon_extent() * self.width as f64) as usize;
+        let y = ((bounds.north - lat) / bounds.lat_extent() * self.height as f64) as usize;
+        Some((x.min(self.width - 1), y.min(self.height - 1)))
+    }
+}
+
+pub(super) fn pixel_lat_lon(
+    width: usize,
+    height: usize,
+    bounds: &GeoBounds,
+    x: usize,
+    y: usize,
+) -> (f64, f64) {
+    let lat = bounds.north - (y as f64 + 0.5) * bounds.lat_extent() / height as f64;
+    let lon = bounds.west + (x as f64 + 0.5) * bounds.lon_extent() / width as f64;
+
+    (lat, lon)
+}
+
+/// Соседство клеток карты высот для обхода по восьми соседям.
+///
+/// Клетки нумеруются построчно, как в [`Heightmap::data`]. Если карта
+/// охватывает все 360° долготы, крайние столбцы соседствуют друг с другом.
+#[derive(Debug, Clone)]
+pub(super) struct Grid {
+    width: usize,
+    height: usize,
+    wraps: bool,
+    /// Границы карты, за которыми нет клеток, но есть продолжение поверхности.
+    open_north: bool,
+    open_south: bool,
+    pixel_height: f64,
+    /// Ширина пикселя в градусах дуги для каждой строки.
+    pixel_widths: Vec<f64>,
+}
+
+impl Grid {
+    pub(super) fn new(heightmap: &Heightmap) -> Self {
+        let (width, height, bounds) = (heightmap.width(), heightmap.height(), heightmap.bounds());
+        let pixel_width = bounds.lon_extent() / width as f64;
+
+        Self {
+            width,
+            height,
+            wraps: super::shading::wraps_around(heightmap),
+            open_north: bounds.north < 90.0,
+            open_south: bounds.south > -90.0,
+            pixel_height: bounds.lat_extent() / height as f64,
+            pixel_widths: (0..height)
+                .map(|y| {
+                    let (lat, _) = heightmap.lat_lon(0, y);
+                    pixel_width * lat.to_radians().cos().max(0.01)
+                })
+                .collect(),
+        }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Соседи клетки `cell` и расстояния до них в градусах дуги.
+    pub(super) fn neighbours(&self, cell: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let (x, y) = ((cell % self.width) as isize, (cell / self.width) as isize);
+        let step_x = self.pixel_widths[y as usize];
+
+        [
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+            (-1, 0),
+            (1, 0),
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+        ]
+        .into_iter()
+        .filter_map(move |(dx, dy)| {
+            let neighbour = self.cell(x + dx, y + dy)?;
+            Some((
+                neighbour,
+                f64::hypot(dx as f64 * step_x, dy as f64 * self.pixel_height),
+            ))
+        })
+    }
+
+    /// Клетка в столбце `x` и строке `y`. На замкнутой карте столбцы
+    /// за краями берутся с противоположного края.
+    /// `None`, если такой клетки нет.
+    pub(super) fn cell(&self, x: isize, y: isize) -> Option<usize> {
+        let width = self.width as isize;
+        if y < 0 || y >= self.height as isize || (!self.wraps && (x < 0 || x >= width)) {
+            return None;
+        }
+
+        Some(y as usize * self.width + x.rem_euclid(width) as usize)
+    }
+
+    /// Ширина и высота пикселей строки `y` в градусах дуги.
+    pub(super) fn pixel_size(&self, y: usize) -> (f64, f64) {
+        (self.pixel_widths[y], self.pixel_height)
+    }
+
+    /// Площадь клетки в квадратных градусах дуги.
+    pub(super) fn area(&self, cell: usize) -> f64 {
+        self.pixel_widths[cell / self.width] * self.pixel_height
+    }
+
+    /// Клетка лежит на краю карты, за которым поверхность планеты продолжается.
+    pub(super) fn is_edge(&self, cell: usize) -> bool {
+        let (x, y) = (cell % self.width, cell / self.width);
+
+        (!self.wraps && (x == 0 || x + 1 == self.width))
+            || (self.open_north && y == 0)
+            || (self.open_south && y + 1 == self.height)
+    }
+}
+
+/// Число с полным порядком для очередей с приоритетом и сортировки.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct OrderedFloat(pub f64);
+
+impl Eq for OrderedFloat {}
+
+impl PartialOrd for OrderedFloat {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrderedFloat {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
@@ -0,0 +1,566 @@
+//! Модуль гидрологии.
+//!
+//! Реки шумового графа (`river_positions` в `continent.rs`) не знают рельефа.
+//! Здесь реки прокладываются по готовой карте высот так, как по ней потекла бы вода:
+//! 1. впадины суши заполняются до уровня перелива (Priority-Flood с малым
+//!    уклоном, чтобы у каждой клетки был сток);
+//! 2. каждая клетка суши сбрасывает воду самому крутому спуску из восьми соседей (D8);
+//! 3. площадь водосбора накапливается вниз по течению;
+//! 4. там, где водосбор больше порога, течет река. Русло врезается в рельеф
+//!    тем глубже, чем больше водосбор, но не глубже `river_depth`.
+//!
+//! Вода уходит в океан (клетки ниже `sea_level`) или за край карты, если карта
+//! охватывает не всю планету. Расстояния и площади измеряются в градусах дуги.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use strum_macros::{Display, EnumCount, EnumIter, EnumString};
+
+use crate::resource::world::heightmap::{Grid, OrderedFloat};
+use crate::resource::world::{Climate, Heightmap, PlanetParams};
+
+/// Параметры гидрологии.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HydrologyConfig {
+    /// Площадь водосбора в квадратных градусах, с которой начинается река.
+    pub river_threshold: f64,
+    /// Площадь водосбора, при которой русло врезается на полную глубину `river_depth`.
+    pub full_depth_area: f64,
+    /// На сколько заполненная впадина поднимается на каждую клетку от места перелива.
+    pub fill_slope: f64,
+}
+
+impl Default for HydrologyConfig {
+    fn default() -> Self {
+        Self {
+            river_threshold: 20.0,
+            full_depth_area: 400.0,
+            fill_slope: 1e-6,
+        }
+    }
+}
+
+/// Вид узла речной сети.
+///
+/// Название вида в `snake_case`, например `"confluence"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumCount, EnumIter, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum RiverNodeKind {
+    /// Исток: выше по течению рек нет.
+    Source,
+    /// Слияние двух и более рек.
+    Confluence,
+    /// Устье: клетка океана, в которую впадает река, или клетка суши на краю карты.
+    Mouth,
+}
+
+/// Узел речной сети.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RiverNode {
+    pub kind: RiverNodeKind,
+    /// Пиксель карты высот.
+    pub x: usize,
+    pub y: usize,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// Участок реки между двумя узлами.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiverSegment {
+    /// Номер узла выше по течению.
+    pub from: usize,
+    /// Номер узла ниже по течению.
+    pub to: usize,
+    /// Пиксели русла по течению, от узла `from` до узла `to` включительно.
+    pub cells: Vec<(usize, usize)>,
+    /// Площадь водосбора в последней клетке суши участка в квадратных градусах.
+    pub discharge: f64,
+}
+
+/// Речная сеть: узлы и участки рек между ними.
+/// Из каждого истока и слияния вниз по течению выходит ровно один участок.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RiverNetwork {
+    nodes: Vec<RiverNode>,
+    segments: Vec<RiverSegment>,
+}
+
+impl RiverNetwork {
+    pub fn nodes(&self) -> &[RiverNode] {
+        &self.nodes
+    }
+
+    pub fn segments(&self) -> &[RiverSegment] {
+        &self.segments
+    }
+
+    pub fn sources(&self) -> impl Iterator<Item = &RiverNode> {
+        self.nodes_of(RiverNodeKind::Source)
+    }
+
+    pub fn confluences(&self) -> impl Iterator<Item = &RiverNode> {
+        self.nodes_of(RiverNodeKind::Confluence)
+    }
+
+    pub fn mouths(&self) -> impl Iterator<Item = &RiverNode> {
+        self.nodes_of(RiverNodeKind::Mouth)
+    }
+
+    fn nodes_of(&self, kind: RiverNodeKind) -> impl Iterator<Item = &RiverNode> {
+        self.nodes.iter().filter(move |node| node.kind == kind)
+    }
+}
+
+/// Результат гидрологического расчета на сетке карты высот.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hydrology {
+    filled: Heightmap,
+    carved: Heightmap,
+    accumulation: Heightmap,
+    /// Клетка, в которую стекает вода из каждой клетки.
+    downstream: Vec<Option<usize>>,
+    rivers: Vec<bool>,
+    network: RiverNetwork,
+}
+
+impl Hydrology {
+    /// Реки по карте высот `heightmap` планеты с параметрами `params`.
+    /// Каждая клетка суши дает воду пропорционально своей площади.
+    pub fn simulate(
+        heightmap: &Heightmap,
+        params: &PlanetParams,
+        config: &HydrologyConfig,
+    ) -> Self {
+        Self::run(heightmap, params, config, |_| 1.0)
+    }
+
+    /// Реки по карте высот `heightmap`, на которой посчитан климат `climate`.
+    /// Каждая клетка суши дает воду пропорционально площади и влажности,
+    /// поэтому в пустынях рек меньше.
+    pub fn simulate_with_climate(
+        heightmap: &Heightmap,
+        params: &PlanetParams,
+        config: &HydrologyConfig,
+        climate: &Climate,
+    ) -> Self {
+        let moisture = climate.moisture().data();
+        Self::run(heightmap, params, config, |cell| moisture[cell] as f64)
+    }
+
+    fn run(
+        heightmap: &Heightmap,
+        params: &PlanetParams,
+        config: &HydrologyConfig,
+        runoff: impl Fn(usize) -> f64,
+    ) -> Self {
+        let (width, height, bounds) = (heightmap.width(), heightmap.height(), heightmap.bounds());
+        let grid = Grid::new(heightmap);
+        let elevation: Vec<f64> = heightmap.data().iter().map(|value| *value as f64).collect();
+        let is_ocean = |cell: usize| elevation[cell] < params.sea_level;
+
+        let filled = fill_depressions(&grid, &elevation, params, config.fill_slope);
+        let downstream = flow_directions(&grid, &filled, is_ocean);
+
+        // Вода стекает только вниз по заполненному рельефу, поэтому при обходе
+        // сверху вниз весь приток в клетку уже известен
+        let mut order: Vec<usize> = (0..grid.len()).collect();
+        order.sort_by_key(|cell| Reverse(OrderedFloat(filled[*cell])));
+
+        let mut accumulation: Vec<f64> = (0..grid.len())
+            .map(|cell| match is_ocean(cell) {
+                true => 0.0,
+                false => grid.area(cell) * runoff(cell),
+            })
+            .collect();
+        for cell in order {
+            if let Some(next) = downstream[cell] {
+                accumulation[next] += accumulation[cell];
+            }
+        }
+
+        let rivers: Vec<bool> = (0..grid.len())
+            .map(|cell| !is_ocean(cell) && accumulation[cell] >= config.river_threshold)
+            .collect();
+
+        let mut carved = heightmap.data().to_vec();
+        for cell in (0..grid.len()).filter(|cell| rivers[*cell]) {
+            let depth = params.river_depth
+                * (accumulation[cell] / config.full_depth_area)
+                    .sqrt()
+                    .min(1.0);
+            carved[cell] = elevation[cell].min(filled[cell] - depth) as f32;
+        }
+
+        let network = river_network(heightmap, &downstream, &rivers, &accumulation, is_ocean);
+
+        let to_heightmap = |values: Vec<f64>| {
+            Heightmap::from_raw(
+                width,
+                height,
+                bounds,
+                values.into_iter().map(|value| value as f32).collect(),
+            )
+        };
+
+        Self {
+            filled: to_heightmap(filled),
+            carved: Heightmap::from_raw(width, height, bounds, carved),
+            accumulation: to_heightmap(accumulation),
+            downstream,
+            rivers,
+            network,
+        }
+    }
+
+    /// Высоты с заполненными впадинами суши.
+    pub fn filled(&self) -> &Heightmap {
+        &self.filled
+    }
+
+    /// Высоты с врезанными руслами рек.
+    pub fn carved(&self) -> &Heightmap {
+        &self.carved
+    }
+
+    /// Площадь водосбора каждой клетки в квадратных градусах.
+    pub fn accumulation(&self) -> &Heightmap {
+        &self.accumulation
+    }
+
+    pub fn network(&self) -> &RiverNetwork {
+        &self.network
+    }
+
+    /// Пиксель, в который стекает вода из пикселя (`x`, `y`).
+    /// `None` для океана и для клеток, из которых вода уходит за край карты.
+    pub fn downstream(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        let width = self.carved.width();
+        self.downstream[y * width + x].map(|cell| (cell % width, cell / width))
+    }
+
+    /// По пикселю (`x`, `y`) течет река.
+    pub fn is_river(&self, x: usize, y: usize) -> bool {
+        self.rivers[y * self.carved.width() + x]
+    }
+}
+
+/// Заполнение впадин суши (Priority-Flood+ε).
+///
+/// Вода заливает сушу от стоков — океана и краев карты — по возрастанию высоты.
+/// Клетка, до которой вода дошла, поднимается хотя бы на `slope` выше клетки,
+/// из которой дошла, поэтому с заполненного рельефа всегда есть спуск к стоку.
+/// При `slope` = 0.0 впадины заполняются ровно до уровня перелива.
+pub(super) fn fill_depressions(
+    grid: &Grid,
+    elevation: &[f64],
+    params: &PlanetParams,
+    slope: f64,
+) -> Vec<f64> {
+    let mut filled = elevation.to_vec();
+    let mut visited = vec![false; grid.len()];
+    let mut queue = BinaryHeap::new();
+
+    for cell in 0..grid.len() {
+        if elevation[cell] < params.sea_level || grid.is_edge(cell) {
+            visited[cell] = true;
+            queue.push((Reverse(OrderedFloat(elevation[cell])), cell));
+        }
+    }
+
+    // Суша без океана и краев стекает в самую низкую точку
+    if queue.is_empty() {
+        if let Some(lowest) = (0..grid.len()).min_by_key(|cell| OrderedFloat(elevation[*cell])) {
+            visited[lowest] = true;
+            queue.push((Reverse(OrderedFloat(elevation[lowest])), lowest));
+        }
+    }
+
+    while let Some((Reverse(OrderedFloat(level)), cell)) = queue.pop() {
+        for (neighbour, _) in grid.neighbours(cell) {
+            if visited[neighbour] {
+                continue;
+            }
+
+            visited[neighbour] = true;
+            filled[neighbour] = elevation[neighbour].max(level + slope);
+            queue.push((Reverse(OrderedFloat(filled[neighbour])), neighbour));
+        }
+    }
+
+    filled
+}
+
+/// Направление стока D8: сосед с самым крутым спуском по заполненному рельефу.
+/// Вода из океана никуда не стекает.
+fn flow_directions(
+    grid: &Grid,
+    filled: &[f64],
+    is_ocean: impl Fn(usize) -> bool,
+) -> Vec<Option<usize>> {
+    (0..grid.len())
+        .map(|cell| {
+            if is_ocean(cell) {
+                return None;
+            }
+
+            grid.neighbours(cell)
+                .map(|(neighbour, distance)| {
+                    (neighbour, (filled[cell] - filled[neighbour]) / distance)
+                })
+                .filter(|(_, drop)| *drop > 0.0)
+                .max_by_key(|(_, drop)| OrderedFloat(*drop))
+                .map(|(neighbour, _)| neighbour)
+        })
+        .collect()
+}
+
+/// Речная сеть по направлениям стока и клеткам рек.
+fn river_network(
+    heightmap: &Heightmap,
+    downstream: &[Option<usize>],
+    rivers: &[bool],
+    accumulation: &[f64],
+    is_ocean: impl Fn(usize) -> bool,
+) -> RiverNetwork {
+    let width = heightmap.width();
+    let pixel = |cell: usize| (cell % width, cell / width);
+
+    let mut tributaries = vec![0; rivers.len()];
+    for cell in (0..rivers.len()).filter(|cell| rivers[*cell]) {
+        if let Some(next) = downstream[cell].filter(|next| rivers[*next]) {
+            tributaries[next] += 1;
+        }
+    }
+
+    let mut network = RiverNetwork::default();
+    let mut node_at = HashMap::new();
+    let mut add_node = |network: &mut RiverNetwork, kind: RiverNodeKind, cell: usize| {
+        *node_at
+            .entry((kind == RiverNodeKind::Mouth, cell))
+            .or_insert_with(|| {
+                let (x, y) = pixel(cell);
+                let (lat, lon) = heightmap.lat_lon(x, y);
+                network.nodes.push(RiverNode {
+                    kind,
+                    x,
+                    y,
+                    lat,
+                    lon,
+                });
+                network.nodes.len() - 1
+            })
+    };
+
+    for start in (0..rivers.len()).filter(|cell| rivers[*cell] && tributaries[*cell] != 1) {
+        let kind = match tributaries[start] {
+            0 => RiverNodeKind::Source,
+            _ => RiverNodeKind::Confluence,
+        };
+        let from = add_node(&mut network, kind, start);
+
+        let mut cells = vec![start];
+        let mut current = start;
+        let to = loop {
+            match downstream[current] {
+                Some(next) if rivers[next] => {
+                    cells.push(next);
+                    if tributaries[next] > 1 {
+                        break add_node(&mut network, RiverNodeKind::Confluence, next);
+                    }
+                    current = next;
+                }
+                Some(next) if is_ocean(next) => {
+                    cells.push(next);
+                    break add_node(&mut network, RiverNodeKind::Mouth, next);
+                }
+                _ => break add_node(&mut network, RiverNodeKind::Mouth, current),
+            }
+        };
+
+        network.segments.push(RiverSegment {
+            from,
+            to,
+            discharge: accumulation[current],
+            cells: cells.into_iter().map(pixel).collect(),
+        });
+    }
+
+    network
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::world::GeoBounds;
+
+    /// Неровная суша 24x16 с ямами и океаном в двух нижних строках.
+    fn rough() -> Heightmap {
+        let data = (0..16)
+            .flat_map(|y| (0..24).map(move |x| (x, y)))
+            .map(|(x, y)| match y {
+                14.. => -0.2,
+                _ => 0.05 + ((x * 7 + y * 13) % 11) as f32 / 50.0,
+            })
+            .collect();
+        Heightmap::from_raw(24, 16, GeoBounds::new(-8.0, 8.0, 0.0, 24.0), data)
+    }
+
+    /// Долина 9x10: два притока сливаются в клетке (4, 4), а река впадает
+    /// в океан в нижней строке. Вне русел суша выше и поднимается от них.
+    fn valley() -> Heightmap {
+        let channel = [
+            (2, 0),
+            (2, 1),
+            (2, 2),
+            (3, 3),
+            (6, 0),
+            (6, 1),
+            (6, 2),
+            (5, 3),
+            (4, 4),
+            (4, 5),
+            (4, 6),
+            (4, 7),
+            (4, 8),
+        ];
+
+        let data = (0..10)
+            .flat_map(|y| (0..9).map(move |x| (x, y)))
+            .map(|(x, y): (i32, i32)| {
+                if y == 9 {
+                    return -0.2;
+                }
+
+                let base = 0.02 * (9 - y) as f32;
+                let distance = channel
+                    .iter()
+                    .map(|(cx, cy)| (x - cx).abs().max((y - cy).abs()))
+                    .min()
+                    .unwrap();
+                match distance {
+                    0 => base,
+                    d => base + 0.1 + 0.01 * d as f32,
+                }
+            })
+            .collect();
+        Heightmap::from_raw(9, 10, GeoBounds::new(-5.0, 5.0, 0.0, 9.0), data)
+    }
+
+    fn simulate(heightmap: &Heightmap, config: &HydrologyConfig) -> Hydrology {
+        Hydrology::simulate(heightmap, &PlanetParams::default(), config)
+    }
+
+    #[test]
+    fn filled_land_always_drains() {
+        let heightmap = rough();
+        let params = PlanetParams::default();
+        let grid = Grid::new(&heightmap);
+        let elevation: Vec<f64> = heightmap.data().iter().map(|value| *value as f64).collect();
+
+        let filled = fill_depressions(&grid, &elevation, &params, 1e-6);
+        assert!((0..grid.len()).any(|cell| filled[cell] > elevation[cell] + 0.01));
+
+        for cell in 0..grid.len() {
+            assert!(filled[cell] >= elevation[cell]);
+            if elevation[cell] < params.sea_level || grid.is_edge(cell) {
+                continue;
+            }
+
+            assert!(
+                grid.neighbours(cell)
+                    .any(|(neighbour, _)| filled[neighbour] < filled[cell]),
+                "cell {} has no lower neighbour",
+                cell
+            );
+        }
+    }
+
+    #[test]
+    fn water_reaches_ocean_or_edge() {
+        let heightmap = rough();
+        let hydrology = simulate(&heightmap, &HydrologyConfig::default());
+        let grid = Grid::new(&heightmap);
+
+        for y in 0..heightmap.height() {
+            for x in 0..heightmap.width() {
+                let (mut current, mut steps) = ((x, y), 0);
+                while let Some(next) = hydrology.downstream(current.0, current.1) {
+                    current = next;
+                    steps += 1;
+                    assert!(steps <= grid.len(), "flow from ({}, {}) loops", x, y);
+                }
+
+                let (x, y) = current;
+                assert!(
+                    heightmap.get(x, y) < 0.0 || grid.is_edge(y * heightmap.width() + x),
+                    "flow ends inland at ({}, {})",
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn accumulation_grows_downstream() {
+        let heightmap = rough();
+        let hydrology = simulate(&heightmap, &HydrologyConfig::default());
+        let accumulation = hydrology.accumulation();
+
+        for y in 0..heightmap.height() {
+            for x in 0..heightmap.width() {
+                if let Some((next_x, next_y)) = hydrology.downstream(x, y) {
+                    assert!(accumulation.get(next_x, next_y) >= accumulation.get(x, y));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tributaries_meet_once() {
+        let config = HydrologyConfig {
+            river_threshold: 6.0,
+            ..HydrologyConfig::default()
+        };
+        let hydrology = simulate(&valley(), &config);
+        let network = hydrology.network();
+
+        let confluences: Vec<_> = network.confluences().map(|node| (node.x, node.y)).collect();
+        let mouths: Vec<_> = network.mouths().map(|node| (node.x, node.y)).collect();
+        assert_eq!(confluences, [(4, 4)]);
+        assert_eq!(mouths, [(4, 9)]);
+        assert_eq!(network.sources().count(), 2);
+        assert_eq!(network.segments().len(), 3);
+    }
+
+    #[test]
+    fn carving_is_limited_by_river_depth() {
+        let heightmap = rough();
+        let params = PlanetParams::default();
+        let config = HydrologyConfig {
+            river_threshold: 2.0,
+            full_depth_area: 10.0,
+            ..HydrologyConfig::default()
+        };
+        let hydrology = simulate(&heightmap, &config);
+
+        let mut deepest = 0.0f64;
+        for y in 0..heightmap.height() {
+            for x in 0..heightmap.width() {
+                let depth = (heightmap.get(x, y) - hydrology.carved().get(x, y)) as f64;
+                assert!(
+                    depth <= params.river_depth + 1e-6,
+                    "{} at ({}, {})",
+                    depth,
+                    x,
+                    y
+                );
+                deepest = deepest.max(depth);
+            }
+        }
+
+        assert!(deepest > params.river_depth / 2.0);
+    }
+}
@@ -0,0 +1,342 @@
+//! Модуль озер.
+//!
+//! Озера — замкнутые котловины суши выше `sea_level`. Котловина заполняется
+//! водой до высоты, на которой вода переливается через край, и через эту
+//! точку (исток) озеро сбрасывает воду дальше. В засушливом климате испарение
+//! не дает котловине наполниться: озеро остается ниже края, стока у него нет,
+//! и оно становится соленым.
+
+use std::collections::{HashSet, VecDeque};
+
+use strum_macros::{Display, EnumCount, EnumIter, EnumString};
+
+use crate::resource::world::heightmap::{Grid, OrderedFloat};
+use crate::resource::world::hydrology::fill_depressions;
+use crate::resource::world::{Climate, GeoBounds, Heightmap, PlanetParams};
+
+/// Параметры поиска озер.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LakeConfig {
+    /// Наименьшая глубина котловины в планетарных единицах высоты.
+    /// Более мелкие впадины остаются сушей.
+    pub min_depth: f64,
+    /// Наименьшая площадь озера в квадратных градусах.
+    pub min_area: f64,
+    /// Влажность, ниже которой озеро бессточное. Котловина заполняется на долю
+    /// `moisture / arid_moisture` своего объема.
+    pub arid_moisture: f64,
+}
+
+impl Default for LakeConfig {
+    fn default() -> Self {
+        Self {
+            min_depth: 0.01,
+            min_area: 2.0,
+            arid_moisture: 0.15,
+        }
+    }
+}
+
+/// Вид озера.
+///
+/// Название вида в `snake_case`, например `"salt"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumCount, EnumIter, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum LakeKind {
+    /// Пресное озеро со стоком.
+    Fresh,
+    /// Бессточное соленое озеро.
+    Salt,
+}
+
+/// Озеро.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lake {
+    pub kind: LakeKind,
+    /// Высота зеркала воды.
+    pub level: f64,
+    /// Площадь зеркала в квадратных градусах.
+    pub area: f64,
+    /// Наибольшая глубина.
+    pub depth: f64,
+    /// Пиксель на краю котловины, через который вода переливается из озера.
+    /// `None` у бессточных озер.
+    pub outlet: Option<(usize, usize)>,
+    /// Пиксели под водой.
+    pub cells: Vec<(usize, usize)>,
+}
+
+/// Озера на сетке карты высот, по которой они найдены.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LakeMap {
+    width: usize,
+    height: usize,
+    bounds: GeoBounds,
+    lakes: Vec<Lake>,
+    /// Номер озера в каждом пикселе.
+    cells: Vec<Option<usize>>,
+}
+
+impl LakeMap {
+    /// Озера на карте высот `heightmap` планеты с параметрами `params`.
+    /// Без климата все котловины заполняются до края.
+    pub fn detect(heightmap: &Heightmap, params: &PlanetParams, config: &LakeConfig) -> Self {
+        Self::run(heightmap, params, config, |_| 1.0)
+    }
+
+    /// Озера на карте высот `heightmap`, на которой посчитан климат `climate`.
+    pub fn detect_with_climate(
+        heightmap: &Heightmap,
+        params: &PlanetParams,
+        config: &LakeConfig,
+        climate: &Climate,
+    ) -> Self {
+        let moisture = climate.moisture().data();
+        Self::run(heightmap, params, config, |cell| moisture[cell] as f64)
+    }
+
+    fn run(
+        heightmap: &Heightmap,
+        params: &PlanetParams,
+        config: &LakeConfig,
+        moisture: impl Fn(usize) -> f64,
+    ) -> Self {
+        let (width, height, bounds) = (heightmap.width(), heightmap.height(), heightmap.bounds());
+        let grid = Grid::new(heightmap);
+        let elevation: Vec<f64> = heightmap.data().iter().map(|value| *value as f64).collect();
+        let filled = fill_depressions(&grid, &elevation, params, 0.0);
+
+        let mut map = Self {
+            width,
+            height,
+            bounds,
+            lakes: Vec::new(),
+            cells: vec![None; grid.len()],
+        };
+
+        let pixel = |cell: usize| (cell % width, cell / width);
+        let mut visited = vec![false; grid.len()];
+        for start in 0..grid.len() {
+            if visited[start] || filled[start] <= elevation[start] {
+                continue;
+            }
+
+            let basin = flood(&grid, &filled, &elevation, &mut visited, start);
+            let spill = filled[start];
+            let bottom = basin
+                .iter()
+                .map(|cell| elevation[*cell])
+                .fold(f64::INFINITY, f64::min);
+            if spill - bottom < config.min_depth {
+                continue;
+            }
+
+            let area: f64 = basin.iter().map(|cell| grid.area(*cell)).sum();
+            let humidity = basin
+                .iter()
+                .map(|cell| moisture(*cell) * grid.area(*cell))
+                .sum::<f64>()
+                / area;
+
+            let (kind, level, outlet) = if humidity < config.arid_moisture {
+                let volume = basin_volume(&grid, &elevation, &basin, spill);
+                let level = level_for_volume(
+                    &grid,
+                    &elevation,
+                    &basin,
+                    (bottom, spill),
+                    volume * humidity / config.arid_moisture,
+                );
+                (LakeKind::Salt, level, None)
+            } else {
+                let outlet = outlet(&grid, &filled, &basin);
+                (LakeKind::Fresh, spill, outlet.map(pixel))
+            };
+
+            let cells: Vec<usize> = basin
+                .into_iter()
+                .filter(|cell| elevation[*cell] < level)
+                .collect();
+            let area: f64 = cells.iter().map(|cell| grid.area(*cell)).sum();
+            if cells.is_empty() || area < config.min_area {
+                continue;
+            }
+
+            for cell in &cells {
+                map.cells[*cell] = Some(map.lakes.len());
+            }
+            map.lakes.push(Lake {
+                kind,
+                level,
+                area,
+                depth: level - bottom,
+                outlet,
+                cells: cells.into_iter().map(pixel).collect(),
+            });
+        }
+
+        map
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn bounds(&self) -> GeoBounds {
+        self.bounds
+    }
+
+    pub fn lakes(&self) -> &[Lake] {
+        &self.lakes
+    }
+
+    /// Озеро, под которым лежит пиксель (`x`, `y`).
+    pub fn lake_at(&self, x: usize, y: usize) -> Option<&Lake> {
+        self.cells[y * self.width + x].map(|lake| &self.lakes[lake])
+    }
+}
+
+/// Котловина с клеткой `start`: все связанные с ней клетки под уровнем заполнения.
+/// У соседних затопленных клеток уровень всегда один и тот же.
+fn flood(
+    grid: &Grid,
+    filled: &[f64],
+    elevation: &[f64],
+    visited: &mut [bool],
+    start: usize,
+) -> Vec<usize> {
+    let mut basin = Vec::new();
+    let mut queue = VecDeque::from([start]);
+    visited[start] = true;
+
+    while let Some(cell) = queue.pop_front() {
+        basin.push(cell);
+        for (neighbour, _) in grid.neighbours(cell) {
+            if !visited[neighbour] && filled[neighbour] > elevation[neighbour] {
+                visited[neighbour] = true;
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    basin
+}
+
+/// Самая низкая по заполненному рельефу клетка вокруг котловины `basin`:
+/// через нее вода переливается через край.
+fn outlet(grid: &Grid, filled: &[f64], basin: &[usize]) -> Option<usize> {
+    let inside: HashSet<usize> = basin.iter().copied().collect();
+
+    basin
+        .iter()
+        .flat_map(|cell| grid.neighbours(*cell))
+        .map(|(neighbour, _)| neighbour)
+        .filter(|neighbour| !inside.contains(neighbour))
+        .min_by_key(|neighbour| (OrderedFloat(filled[*neighbour]), *neighbour))
+}
+
+/// Объем воды в котловине `basin` при уровне `level`.
+fn basin_volume(grid: &Grid, elevation: &[f64], basin: &[usize], level: f64) -> f64 {
+    basin
+        .iter()
+        .map(|cell| (level - elevation[*cell]).max(0.0) * grid.area(*cell))
+        .sum()
+}
+
+/// Уровень, при котором в котловине `basin` ровно `volume` воды.
+/// Объем растет с уровнем, поэтому уровень ищется делением отрезка `range` пополам.
+fn level_for_volume(
+    grid: &Grid,
+    elevation: &[f64],
+    basin: &[usize],
+    (mut low, mut high): (f64, f64),
+    volume: f64,
+) -> f64 {
+    for _ in 0..50 {
+        let middle = (low + high) / 2.0;
+        if basin_volume(grid, elevation, basin, middle) < volume {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+
+    (low + high) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Котловина 9x9 с пикселями в 1°: дно на высоте 0.1 в центре, кольцо края
+    /// на высоте `rim` в трех пикселях от центра, за ним склон к краям карты.
+    fn bowl(rim: f32) -> Heightmap {
+        let data = (0..9)
+            .flat_map(|y| (0..9).map(move |x| (x, y)))
+            .map(
+                |(x, y): (i32, i32)| match (x - 4).abs().max((y - 4).abs()) {
+                    3 => rim,
+                    4 => 0.05,
+                    d => 0.1 + 0.001 * d as f32,
+                },
+            )
+            .collect();
+        Heightmap::from_raw(9, 9, GeoBounds::new(-4.5, 4.5, 0.0, 9.0), data)
+    }
+
+    fn detect(heightmap: &Heightmap, moisture: f64) -> LakeMap {
+        LakeMap::run(
+            heightmap,
+            &PlanetParams::default(),
+            &LakeConfig::default(),
+            |_| moisture,
+        )
+    }
+
+    fn on_rim((x, y): (usize, usize)) -> bool {
+        (x as i32 - 4).abs().max((y as i32 - 4).abs()) == 3
+    }
+
+    #[test]
+    fn closed_bowl_holds_fresh_lake() {
+        let lakes = detect(&bowl(0.3), 1.0);
+        assert_eq!(lakes.lakes().len(), 1);
+
+        let lake = &lakes.lakes()[0];
+        assert_eq!(lake.kind, LakeKind::Fresh);
+        assert_eq!(lake.level, 0.3f32 as f64);
+        assert!((lake.depth - (0.3 - 0.1)).abs() < 1e-6);
+        assert_eq!(lake.cells.len(), 25);
+        assert!(on_rim(lake.outlet.unwrap()));
+        assert_eq!(lakes.lake_at(4, 4), Some(lake));
+        assert_eq!(lakes.lake_at(4, 1), None);
+    }
+
+    #[test]
+    fn arid_bowl_holds_salt_lake() {
+        let lakes = detect(&bowl(0.3), 0.05);
+        assert_eq!(lakes.lakes().len(), 1);
+
+        let lake = &lakes.lakes()[0];
+        assert_eq!(lake.kind, LakeKind::Salt);
+        assert_eq!(lake.outlet, None);
+        assert!(lake.level > 0.1 && lake.level < 0.3f32 as f64);
+    }
+
+    #[test]
+    fn shallow_bowl_has_no_lake() {
+        let heightmap = bowl(0.105);
+        assert!(detect(&heightmap, 1.0).lakes().is_empty());
+
+        let config = LakeConfig {
+            min_depth: 0.001,
+            ..LakeConfig::default()
+        };
+        let lakes = LakeMap::detect(&heightmap, &PlanetParams::default(), &config);
+        assert_eq!(lakes.lakes().len(), 1);
+    }
+}
@@ -0,0 +1,98 @@
+//! Модуль отладочных слоев шумового графа.
+//!
+//! Каждый слой — закешированный промежуточный этап графа континентов.
+//! Слои можно сэмплировать и рендерить по отдельности, чтобы видеть, какой этап
+//! отвечает за ту или иную деталь итогового ландшафта.
+
+use strum_macros::{Display, EnumCount, EnumIter, EnumString};
+
+/// Промежуточный этап шумового графа планеты.
+///
+/// Название слоя в `snake_case` совпадает с именем этапа в графе континентов,
+/// например `"mountainous_terrain"`. Индекс слоя (`layer as usize`) не меняется
+/// между версиями, поэтому новые слои добавляются только в конец.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumCount, EnumIter, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum TerrainLayer {
+    /// Базовое определение континентов до искажения.
+    BaseContinentDef,
+    /// Искаженное определение континентов.
+    ContinentDef,
+    /// Тип местности: чем выше значение, тем более грубая местность.
+    TerrainTypeDef,
+    /// Горные хребты до добавления высоких и низких гор.
+    MountainBaseDef,
+    /// Горная местность.
+    MountainousTerrain,
+    /// Холмистая местность.
+    HillyTerrain,
+    /// Равнины.
+    PlainsTerrain,
+    /// Бесплодные земли.
+    BadlandsTerrain,
+    /// Положения рек.
+    RiverPositions,
+    /// Горная местность, масштабированная под итоговую планету.
+    ScaledMountainousTerrain,
+    /// Холмистая местность, масштабированная под итоговую планету.
+    ScaledHillyTerrain,
+    /// Итоговая высота планеты.
+    Elevation,
+    /// Положение бесплодных земель: они появляются там, где значение выше
+    /// `1.0 - badlands_amount`.
+    BadlandsMask,
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::{EnumCount, IntoEnumIterator};
+
+    use super::*;
+    use crate::resource::world::{GraphDocument, Planet, WorldBuilder};
+
+    #[test]
+    fn layer_indices_are_stable() {
+        let layers = [
+            TerrainLayer::BaseContinentDef,
+            TerrainLayer::ContinentDef,
+            TerrainLayer::TerrainTypeDef,
+            TerrainLayer::MountainBaseDef,
+            TerrainLayer::MountainousTerrain,
+            TerrainLayer::HillyTerrain,
+            TerrainLayer::PlainsTerrain,
+            TerrainLayer::BadlandsTerrain,
+            TerrainLayer::RiverPositions,
+            TerrainLayer::ScaledMountainousTerrain,
+            TerrainLayer::ScaledHillyTerrain,
+            TerrainLayer::Elevation,
+            TerrainLayer::BadlandsMask,
+        ];
+
+        assert_eq!(layers.len(), TerrainLayer::COUNT);
+        for (index, layer) in layers.into_iter().enumerate() {
+            assert_eq!(layer as usize, index, "{}", layer);
+        }
+    }
+
+    /// Каждый слой графа континентов должен сэмплировать тот же узел, что и слой
+    /// с тем же именем в документе графа, где слои связаны с узлами по имени.
+    #[test]
+    fn layers_sample_their_nodes() {
+        let builder = WorldBuilder::new().set_seed(7);
+        let planet: Planet = builder.clone().build().unwrap();
+        let document = builder.set_graph(GraphDocument::default()).build().unwrap();
+
+        for layer in TerrainLayer::iter() {
+            for (lat, lon) in [(0.0, 0.0), (35.5, -120.25), (-60.0, 75.0)] {
+                assert_eq!(
+                    planet.sample_layer(layer, lat, lon).to_bits(),
+                    document.sample_layer(layer, lat, lon).to_bits(),
+                    "{} at {}, {}",
+                    layer,
+                    lat,
+                    lon
+                );
+            }
+        }
+    }
+}
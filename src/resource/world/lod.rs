@@ -0,0 +1,426 @@
+//! Модуль уровней детализации поверхности планеты.
+//!
+//! Поверхность делится на шесть граней куба, спроецированного на сферу. Каждая
+//! грань — корень квадродерева: чем ближе точка обзора к участку, тем глубже он
+//! делится. Модуль не зависит от графики, решения о делении и слиянии участков
+//! принимаются по одним только координатам.
+
+use std::collections::BTreeSet;
+use std::f64::consts::FRAC_PI_2;
+
+use strum::IntoEnumIterator;
+use strum_macros::{EnumCount, EnumIter};
+
+/// Грань куба.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, EnumCount, EnumIter)]
+pub enum CubeFace {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl CubeFace {
+    /// Точка грани с координатами `u` и `v` от -1.0 до 1.0.
+    ///
+    /// Базис грани правый: обход (0, 0) → (1, 0) → (1, 1) идет против часовой
+    /// стрелки, если смотреть на грань снаружи куба.
+    pub fn cube_point(self, u: f64, v: f64) -> [f64; 3] {
+        let (axis, side) = match self {
+            Self::PosX => (0, 1.0),
+            Self::NegX => (0, -1.0),
+            Self::PosY => (1, 1.0),
+            Self::NegY => (1, -1.0),
+            Self::PosZ => (2, 1.0),
+            Self::NegZ => (2, -1.0),
+        };
+
+        // У отрицательных граней оси базиса меняются местами, чтобы он остался правым
+        let (u, v) = if side > 0.0 { (u, v) } else { (v, u) };
+
+        let mut point = [0.0; 3];
+        point[axis] = side;
+        point[(axis + 1) % 3] = u;
+        point[(axis + 2) % 3] = v;
+        point
+    }
+}
+
+/// Точка куба [-1, 1]^3 на единичной сфере.
+/// Проекция растягивает центры граней меньше, чем простая нормализация.
+pub fn cube_to_sphere([x, y, z]: [f64; 3]) -> [f64; 3] {
+    let (x2, y2, z2) = (x * x, y * y, z * z);
+
+    normalize([
+        x * (1.0 - y2 / 2.0 - z2 / 2.0 + y2 * z2 / 3.0).sqrt(),
+        y * (1.0 - z2 / 2.0 - x2 / 2.0 + z2 * x2 / 3.0).sqrt(),
+        z * (1.0 - x2 / 2.0 - y2 / 2.0 + x2 * y2 / 3.0).sqrt(),
+    ])
+}
+
+/// Участок поверхности: узел квадродерева грани `face`.
+///
+/// На уровне `level` грань делится на 2^level x 2^level участков,
+/// `x` и `y` — номер участка вдоль осей `u` и `v` грани.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChunkId {
+    pub face: CubeFace,
+    pub level: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl ChunkId {
+    /// Самый глубокий уровень, на котором число участков вдоль грани помещается в `u32`.
+    pub const MAX_LEVEL: u8 = 31;
+
+    /// Участок во всю грань `face`.
+    pub fn root(face: CubeFace) -> Self {
+        Self {
+            face,
+            level: 0,
+            x: 0,
+            y: 0,
+        }
+    }
+
+    /// Четыре участка следующего уровня, на которые делится этот.
+    pub fn children(&self) -> [Self; 4] {
+        [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| Self {
+            face: self.face,
+            level: self.level + 1,
+            x: self.x * 2 + dx,
+            y: self.y * 2 + dy,
+        })
+    }
+
+    /// Участок предыдущего уровня, содержащий этот.
+    pub fn parent(&self) -> Option<Self> {
+        (self.level > 0).then(|| Self {
+            face: self.face,
+            level: self.level - 1,
+            x: self.x / 2,
+            y: self.y / 2,
+        })
+    }
+
+    /// Лежит ли участок `other` внутри этого участка или совпадает с ним.
+    pub fn contains(&self, other: &ChunkId) -> bool {
+        self.face == other.face
+            && self.level <= other.level
+            && other.x >> (other.level - self.level) == self.x
+            && other.y >> (other.level - self.level) == self.y
+    }
+
+    /// Точка участка на единичной сфере. `s` и `t` от 0.0 до 1.0 идут вдоль осей `u` и `v` грани.
+    pub fn point(&self, s: f64, t: f64) -> [f64; 3] {
+        let cells = f64::from(1u32 << self.level);
+        let u = (f64::from(self.x) + s) / cells * 2.0 - 1.0;
+        let v = (f64::from(self.y) + t) / cells * 2.0 - 1.0;
+
+        cube_to_sphere(self.face.cube_point(u, v))
+    }
+
+    /// Центр участка на единичной сфере.
+    pub fn center(&self) -> [f64; 3] {
+        self.point(0.5, 0.5)
+    }
+
+    /// Примерная длина стороны участка на единичной сфере.
+    pub fn size(&self) -> f64 {
+        FRAC_PI_2 / f64::from(1u32 << self.level)
+    }
+}
+
+/// Настройки деления участков.
+///
+/// Расстояния измеряются в радиусах планеты от точки обзора до центра участка.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LodSettings {
+    /// Самый глубокий уровень деления, не больше [`ChunkId::MAX_LEVEL`].
+    pub max_level: u8,
+    /// Участок делится, когда ближе `split_distance` своих размеров.
+    pub split_distance: f64,
+    /// Разделенный участок сливается, когда дальше `merge_distance` своих размеров.
+    /// Должно быть больше `split_distance`, чтобы участки не мигали на границе.
+    pub merge_distance: f64,
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self {
+            max_level: 8,
+            split_distance: 2.0,
+            merge_distance: 2.5,
+        }
+    }
+}
+
+/// Изменение набора видимых участков.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LodUpdate {
+    /// Новые участки, для которых нужна сетка.
+    pub added: Vec<ChunkId>,
+    /// Участки, которые больше не нужны.
+    pub removed: Vec<ChunkId>,
+}
+
+impl LodUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Квадродеревья всех граней планеты.
+///
+/// Хранит только листья — участки, которые сейчас показываются.
+/// Листья всегда покрывают всю сферу без пересечений.
+#[derive(Debug, Clone)]
+pub struct ChunkQuadtree {
+    settings: LodSettings,
+    leaves: BTreeSet<ChunkId>,
+}
+
+impl ChunkQuadtree {
+    /// Дерево из шести неразделенных граней.
+    /// `max_level` больше [`ChunkId::MAX_LEVEL`] уменьшается до него.
+    pub fn new(mut settings: LodSettings) -> Self {
+        settings.max_level = settings.max_level.min(ChunkId::MAX_LEVEL);
+
+        Self {
+            settings,
+            leaves: roots().collect(),
+        }
+    }
+
+    pub fn settings(&self) -> &LodSettings {
+        &self.settings
+    }
+
+    /// Участки, которые сейчас показываются.
+    pub fn leaves(&self) -> impl Iterator<Item = &ChunkId> {
+        self.leaves.iter()
+    }
+
+    /// Нужно ли разделить неразделенный участок `chunk` при точке обзора `viewpoint`.
+    pub fn should_split(&self, chunk: &ChunkId, viewpoint: [f64; 3]) -> bool {
+        chunk.level < self.settings.max_level
+            && distance(viewpoint, chunk.center()) < self.settings.split_distance * chunk.size()
+    }
+
+    /// Нужно ли слить разделенный участок `chunk` при точке обзора `viewpoint`.
+    pub fn should_merge(&self, chunk: &ChunkId, viewpoint: [f64; 3]) -> bool {
+        distance(viewpoint, chunk.center()) > self.settings.merge_distance * chunk.size()
+    }
+
+    /// Перестроение дерева для точки обзора `viewpoint` в радиусах планеты.
+    /// Участок может разделиться или слиться сразу на несколько уровней.
+    pub fn update(&mut self, viewpoint: [f64; 3]) -> LodUpdate {
+        let mut leaves = BTreeSet::new();
+        for root in roots() {
+            self.refine(root, true, viewpoint, &mut leaves);
+        }
+
+        let update = LodUpdate {
+            added: leaves.difference(&self.leaves).copied().collect(),
+            removed: self.leaves.difference(&leaves).copied().collect(),
+        };
+        self.leaves = leaves;
+        update
+    }
+
+    /// Спуск от участка `chunk` до новых листьев.
+    /// `in_tree` — участок есть в текущем дереве как лист или разделенный узел.
+    fn refine(
+        &self,
+        chunk: ChunkId,
+        in_tree: bool,
+        viewpoint: [f64; 3],
+        leaves: &mut BTreeSet<ChunkId>,
+    ) {
+        let subdivided = in_tree && !self.leaves.contains(&chunk);
+        let split = if subdivided {
+            !self.should_merge(&chunk, viewpoint)
+        } else {
+            self.should_split(&chunk, viewpoint)
+        };
+
+        if split {
+            for child in chunk.children() {
+                self.refine(child, subdivided, viewpoint, leaves);
+            }
+        } else {
+            leaves.insert(chunk);
+        }
+    }
+}
+
+fn roots() -> impl Iterator<Item = ChunkId> {
+    CubeFace::iter().map(ChunkId::root)
+}
+
+fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    (0..3).map(|i| (a[i] - b[i]).powi(2)).sum::<f64>().sqrt()
+}
+
+fn normalize(vector: [f64; 3]) -> [f64; 3] {
+    let length = distance(vector, [0.0; 3]);
+    vector.map(|axis| axis / length)
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::EnumCount;
+
+    use super::*;
+
+    /// Точка обзора над центром участка `chunk` на расстоянии `sizes` его размеров.
+    fn above(chunk: &ChunkId, sizes: f64) -> [f64; 3] {
+        chunk
+            .center()
+            .map(|axis| axis * (1.0 + sizes * chunk.size()))
+    }
+
+    /// Листья покрывают все грани целиком и не пересекаются.
+    fn assert_covers_sphere(quadtree: &ChunkQuadtree) {
+        let leaves: Vec<_> = quadtree.leaves().collect();
+
+        let area: f64 = leaves
+            .iter()
+            .map(|leaf| 0.25f64.powi(i32::from(leaf.level)))
+            .sum();
+        assert_eq!(area, CubeFace::COUNT as f64);
+
+        for (i, a) in leaves.iter().enumerate() {
+            for b in &leaves[i + 1..] {
+                assert!(!a.contains(b) && !b.contains(a), "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn split_and_merge_hysteresis() {
+        let quadtree = ChunkQuadtree::new(LodSettings::default());
+        let chunk = ChunkId::root(CubeFace::PosX);
+
+        assert!(quadtree.should_split(&chunk, above(&chunk, 1.9)));
+        assert!(!quadtree.should_merge(&chunk, above(&chunk, 1.9)));
+
+        // Между порогами участок не делится и не сливается.
+        assert!(!quadtree.should_split(&chunk, above(&chunk, 2.2)));
+        assert!(!quadtree.should_merge(&chunk, above(&chunk, 2.2)));
+
+        assert!(!quadtree.should_split(&chunk, above(&chunk, 2.6)));
+        assert!(quadtree.should_merge(&chunk, above(&chunk, 2.6)));
+    }
+
+    #[test]
+    fn update_splits_toward_viewpoint_and_merges_away() {
+        let mut quadtree = ChunkQuadtree::new(LodSettings::default());
+        let target = ChunkId::root(CubeFace::PosY).children()[3].children()[0];
+        let viewpoint = above(&target, 0.1);
+
+        let update = quadtree.update(viewpoint);
+        assert!(update.removed.contains(&ChunkId::root(CubeFace::PosY)));
+        assert!(!update.added.is_empty());
+        assert_covers_sphere(&quadtree);
+
+        // Участок под точкой обзора — самый глубокий из листьев.
+        let deepest = quadtree.leaves().map(|leaf| leaf.level).max().unwrap();
+        let under = quadtree
+            .leaves()
+            .find(|leaf| target.contains(leaf))
+            .copied()
+            .unwrap();
+        assert!(under.level > target.level);
+        assert!(quadtree
+            .leaves()
+            .filter(|leaf| leaf.level == deepest)
+            .all(|leaf| target.parent().unwrap().contains(leaf)));
+
+        // Повторное обновление с той же точкой ничего не меняет.
+        assert!(quadtree.update(viewpoint).is_empty());
+
+        let update = quadtree.update([0.0, -100.0, 0.0]);
+        assert!(update.added.contains(&ChunkId::root(CubeFace::PosY)));
+        assert!(update.removed.contains(&under));
+        assert_eq!(quadtree.leaves().count(), CubeFace::COUNT);
+        assert_covers_sphere(&quadtree);
+    }
+
+    #[test]
+    fn update_keeps_split_chunks_between_thresholds() {
+        let mut quadtree = ChunkQuadtree::new(LodSettings::default());
+        let chunk = ChunkId::root(CubeFace::NegZ);
+
+        quadtree.update(above(&chunk, 1.9));
+        assert!(!quadtree.leaves().any(|leaf| *leaf == chunk));
+
+        // Слияние только за `merge_distance`, а не сразу за `split_distance`.
+        assert!(quadtree
+            .update(above(&chunk, 2.2))
+            .removed
+            .iter()
+            .all(|leaf| leaf.level > 1));
+        assert!(!quadtree.leaves().any(|leaf| *leaf == chunk));
+
+        quadtree.update(above(&chunk, 2.6));
+        assert!(quadtree.leaves().any(|leaf| *leaf == chunk));
+    }
+
+    #[test]
+    fn update_stops_at_max_level() {
+        let settings = LodSettings {
+            max_level: 3,
+            ..LodSettings::default()
+        };
+        let mut quadtree = ChunkQuadtree::new(settings);
+        let viewpoint = CubeFace::NegX.cube_point(0.3, -0.6);
+
+        quadtree.update(cube_to_sphere(viewpoint));
+        assert_eq!(quadtree.leaves().map(|leaf| leaf.level).max(), Some(3));
+        assert_covers_sphere(&quadtree);
+
+        let leaf = quadtree.leaves().find(|leaf| leaf.level == 3).unwrap();
+        assert!(!quadtree.should_split(leaf, leaf.center()));
+    }
+
+    /// Уровни глубже [`ChunkId::MAX_LEVEL`] не помещаются в `u32`.
+    #[test]
+    fn max_level_is_clamped() {
+        let settings = LodSettings {
+            max_level: u8::MAX,
+            ..LodSettings::default()
+        };
+        let mut quadtree = ChunkQuadtree::new(settings);
+        assert_eq!(quadtree.settings().max_level, ChunkId::MAX_LEVEL);
+
+        let viewpoint = cube_to_sphere(CubeFace::NegX.cube_point(0.3, -0.6));
+        quadtree.update(viewpoint);
+
+        let leaf = quadtree
+            .leaves()
+            .max_by_key(|leaf| leaf.level)
+            .copied()
+            .unwrap();
+        assert_eq!(leaf.level, ChunkId::MAX_LEVEL);
+        assert!(!quadtree.should_split(&leaf, viewpoint));
+    }
+
+    #[test]
+    fn chunk_contains_descendants() {
+        let root = ChunkId::root(CubeFace::PosZ);
+        let child = root.children()[2];
+        let grandchild = child.children()[1];
+
+        assert!(root.contains(&root));
+        assert!(root.contains(&grandchild));
+        assert!(child.contains(&grandchild));
+        assert!(!grandchild.contains(&child));
+        assert!(!root.children()[0].contains(&grandchild));
+        assert!(!ChunkId::root(CubeFace::NegZ).contains(&grandchild));
+        assert_eq!(grandchild.parent(), Some(child));
+    }
+}
@@ -1,101 +1,65 @@
+//! Модуль генерации мира.
+//!
+//! Мир строится через [`WorldBuilder`], результатом является [`Planet`],
+//! которая отвечает на запросы высоты в любой точке поверхности.
+
+mod biome;
+mod climate;
+mod code;
 mod continent;
+mod erosion;
+mod export;
+mod expr;
+mod graph;
+mod heightmap;
+mod hydrology;
+mod lake;
+mod layer;
+mod lod;
+mod params;
+mod planet;
+mod preset;
+mod projection;
+mod render;
+mod sampler;
+mod shading;
+
+pub use biome::{slope_from_tilt, Biome, BiomeClassifier, BiomeMap, BiomePalette};
+pub use climate::{Climate, ClimateConfig, ClimateLayer};
+pub use code::WorldCodeError;
+pub use erosion::{Erosion, ErosionConfig, ErosionLayer};
+pub use graph::{GraphDocument, GraphError};
+pub use heightmap::{GeoBounds, Heightmap};
+pub use hydrology::{
+    Hydrology, HydrologyConfig, RiverNetwork, RiverNode, RiverNodeKind, RiverSegment,
+};
+pub use lake::{Lake, LakeConfig, LakeKind, LakeMap};
+pub use layer::TerrainLayer;
+pub use lod::{cube_to_sphere, ChunkId, ChunkQuadtree, CubeFace, LodSettings, LodUpdate};
+pub use params::{PlanetParams, WorldParamError};
+pub use planet::Planet;
+pub use preset::{PlanetPreset, PresetError, PresetLibrary};
+pub use projection::{Equirectangular, MapProjection, Mercator, Mollweide, Orthographic};
+pub use render::{Color, MapImage, MapRenderer, RenderError, TerrainGradient};
+pub use sampler::ChunkedSampler;
+pub use shading::LightSource;
 
-use lazy_static::lazy_static;
 use rand::Rng;
 
-lazy_static! {
+/// Конструктор мира.
+#[derive(Debug, Clone)]
+pub struct WorldBuilder {
     /// Сид ключ для уникальной генерации планетарного ландшафта
-    pub static ref CURRENT_SEED: u32 = 0;
-
-    /// Частота континентов планеты. Более высокая частота производит
-    /// более мелкие и многочисленные континенты.
-    /// Значение измеряется в радианах.
-    pub(super) static ref CONTINENT_FREQUENCY: f64 = 1.0;
-
-    /// Лакунарность континентов планеты. Изменение этого значения приводит к
-    /// немного разные континенты. Для достижения наилучших результатов это значение должно
-    /// быть случайным, но близким к 2.0.
-    pub(super) static ref CONTINENT_LACUNARITY: f64 = 2.208984375;
-
-    /// Лакунарность гор планеты. Изменение значения производит
-    /// немного другие горы. Для достижения наилучших результатов это значение должно
-    /// быть случайным, но близким к 2.0.
-    pub(super) static ref MOUNTAIN_LACUNARITY: f64 = 2.142578125;
-
-    /// Лакунарность холмов планеты. Изменение этого значения приводит к
-    /// генерации других холмов. Для достижения наилучших результатов это значение должно быть
-    /// случайно, но близко к 2.0.
-    pub(super) static ref HILLS_LACUNARITY: f64 = 2.162109375;
-
-    /// Лакунарность равнин планеты. Изменение этого значения приводит к
-    /// генерации других равнин. Для достижения наилучших результатов это значение должно быть
-    /// случайно, но близко к 2.0.
-    pub(super) static ref PLAINS_LACUNARITY: f64 = 2.314453125;
-
-    /// Лакунарность бесплодных земель планеты. Изменение этого значения приводит к
-    /// генерации других бесплодных земль. Для достижения наилучших результатов это значение должно
-    /// быть случайным, но близким к 2.0.
-    pub(super) static ref BADLANDS_LACUNARITY: f64 = 2.212890625;
-
-    /// Определяет "извилистость" гор.
-    pub(super) static ref MOUNTAINS_TWIST: f64 = 1.0;
-
-    /// Определяет «извилистость» холмов.
-    pub(super) static ref HILLS_TWIST: f64 = 1.0;
-
-    /// Определяет «извилистость» бесплодных земель.
-    pub(super) static ref BADLANDS_TWIST: f64 = 1.0;
-
-    /// Определяет уровень моря на планете. Это значение должно быть между -1,0
-    /// (минимальная высота планеты) и +1.0 (максимальная высота планеты).
-    pub(super) static ref SEA_LEVEL: f64 = 0.0;
-
-    /// Указывает уровень на планете, на котором появляются континентальные шельфы.
-    /// Это значение должно быть между -1,0 (минимальная высота планеты) и +1,0
-    /// (максимальная высота планеты) и должно быть меньше `SEA_LEVEL`.
-    pub(super) static ref SHELF_LEVEL: f64 = -0.375;
-
-    /// Определяет количество гористой местности, которая появляется на
-    /// планета. Значения варьируются от 0,0 (горы отсутствуют) до 1,0 (вся местность
-    /// покрыто горами). Горный рельеф будет перекрывать холмистую местность.
-    /// Поскольку местность бесплодных земель может перекрывать части горной местности
-    /// местность, установка `MOUNTAINS_AMOUNT` на 1.0 может не полностью покрывать
-    /// местность в горах.
-    pub(super) static ref MOUNTAINS_AMOUNT: f64 = 0.48;
-
-    /// Определяет количество холмистой местности, которая появляется на планете.
-    /// Значения варьируются от 0,0 (холмы отсутствуют) до 1,0 (вся местность покрыта
-    /// холмы). Это значение должно быть меньше `MOUNTAINS_AMOUNT`. Поскольку
-    /// горный рельеф будет перекрывать части холмистой местности, а
-    /// ландшафт бесплодных земель может перекрывать части холмистой местности, устанавливая
-    /// `HILLS_AMOUNT` на 1.0 может не полностью покрывать холмистую местность.
-    pub(super) static ref HILLS_AMOUNT: f64 = (1.0 + *MOUNTAINS_AMOUNT) / 2.0;
-
-    /// Определяет количество бесплодных земель, покрывающих планету.
-    /// Значения варьируются от 0,0 (без бесплодных земель) до 1,0 (вся местность покрыта
-    /// бесплодные земли). Ландшафт бесплодных земель будет накладываться на любой другой тип ландшафта.
-    pub(super) static ref BADLANDS_AMOUNT: f64 = 0.3125;
-
-    /// Смещение для применения к определению типа ландшафта. Низкие значения (< 1,0)
-    /// заставляют шероховатые области появляться только на больших высотах. Высокие значения
-    /// (> 2.0) заставляют шероховатые области появляться на любой высоте.
-    /// процент грубых участков на планете не зависит от этого значения.
-    pub(super) static ref TERRAIN_OFFSET: f64 = 1.0;
-
-    /// Определяет количество "оледенения" в горах. Это значение
-    /// должен быть близок к 1,0 и больше 1,0.
-    pub(super) static ref MOUNTAIN_GLACIATION: f64 = 0.375;
-
-    /// Масштабирование для применения к высотам базового континента в планетарных
-    /// единицы высоты.
-    pub(super) static ref CONTINENT_HEIGHT_SCALE: f64 = (1.0 - *SEA_LEVEL) / 4.0;
-
-    /// Максимальная глубина рек в планетарных единицах высоты.
-    pub(super) static ref RIVER_DEPTH: f64 = 0.0234375;
+    current_seed: u32,
+    params: PlanetParams,
+    /// Шумовой граф из документа. Без него используется граф из `continent.rs`.
+    graph: Option<GraphDocument>,
 }
 
-pub struct WorldBuilder {
-    current_seed: u32,
+impl Default for WorldBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl WorldBuilder {
@@ -106,6 +70,8 @@ impl WorldBuilder {
 
         Self {
             current_seed: rng.gen::<u32>(),
+            params: PlanetParams::default(),
+            graph: None,
         }
     }
 
@@ -114,4 +80,122 @@ impl WorldBuilder {
         self.current_seed = seed;
         self
     }
+
+    /// Функция позволяющая указать seed ключ в виде текста, например "frozen-archipelago".
+    /// Текст переводится в число 32-битным хешем FNV-1a от его UTF-8 байт.
+    pub fn set_seed_str(self, seed: &str) -> Self {
+        self.set_seed(code::fnv1a(seed.as_bytes()))
+    }
+
+    /// Создание конструктора из кода мира, полученного через [`WorldBuilder::world_code`].
+    pub fn from_world_code(world_code: &str) -> Result<Self, WorldCodeError> {
+        let (current_seed, params) = code::decode(world_code)?;
+
+        Ok(Self {
+            current_seed,
+            params,
+            graph: None,
+        })
+    }
+
+    /// Короткий код, по которому можно восстановить точно такой же мир.
+    pub fn world_code(&self) -> String {
+        code::encode(self.current_seed, &self.params)
+    }
+
+    /// Функция позволяющая указать свои параметры генерации мира.
+    pub fn set_params(mut self, params: PlanetParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Функция позволяющая взять параметры генерации из пресета `name` набора `library`.
+    pub fn set_preset(self, library: &PresetLibrary, name: &str) -> Result<Self, PresetError> {
+        Ok(self.set_params(library.resolve(name)?))
+    }
+
+    /// Функция позволяющая построить мир по своему шумовому графу.
+    /// Код мира граф не хранит, восстанавливаются только seed ключ и параметры.
+    pub fn set_graph(mut self, graph: GraphDocument) -> Self {
+        self.graph = Some(graph);
+        self
+    }
+
+    /// Seed ключ, с которым будет построен мир.
+    pub fn seed(&self) -> u32 {
+        self.current_seed
+    }
+
+    /// Параметры, с которыми будет построен мир.
+    pub fn params(&self) -> &PlanetParams {
+        &self.params
+    }
+
+    /// Построение планеты.
+    /// Проверяет параметры, собирает полный шумовой граф и передает владение им планете.
+    pub fn build(self) -> Result<Planet, WorldParamError> {
+        self.params.validate()?;
+        if let Some(document) = &self.graph {
+            if let Err(GraphError::TooFewPoints {
+                required, points, ..
+            }) = document.check_points(&self.params)
+            {
+                return Err(WorldParamError::TooFewGraphPoints { required, points });
+            }
+        }
+
+        let mut graph = planet::NoiseGraph::new();
+        // SAFETY: модули слоев передаются планете вместе с хранилищем `graph`,
+        // а планета уничтожает хранилище после ссылок на модули.
+        let layers = unsafe {
+            match &self.graph {
+                Some(document) => document.build(&mut graph, &self.params, self.current_seed),
+                None => continent::continent_definition::base_continent_definition(
+                    &mut graph,
+                    &self.params,
+                    self.current_seed,
+                ),
+            }
+        };
+
+        Ok(Planet::new(self.params, graph, layers))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(seed: u32) -> Vec<u32> {
+        let planet = WorldBuilder::new().set_seed(seed).build().unwrap();
+
+        planet
+            .render_heightmap(32, 16)
+            .data()
+            .iter()
+            .map(|value| value.to_bits())
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_same_planet() {
+        assert_eq!(render(1234), render(1234));
+    }
+
+    #[test]
+    fn different_seed_different_planet() {
+        assert_ne!(render(1234), render(1235));
+    }
+
+    #[test]
+    fn world_code_restores_planet() {
+        let builder = WorldBuilder::new().set_seed_str("frozen-archipelago");
+        let restored = WorldBuilder::from_world_code(&builder.world_code()).unwrap();
+
+        assert_eq!(restored.seed(), builder.seed());
+        assert_eq!(
+            restored.build().unwrap().render_heightmap(16, 8),
+            builder.build().unwrap().render_heightmap(16, 8)
+        );
+    }
 }
@@ -0,0 +1,426 @@
+//! Модуль параметров планеты
+
+use std::fmt;
+
+/// Параметры генерации планеты.
+///
+/// Каждый мир несет собственный набор параметров, поэтому в одном процессе
+/// могут одновременно существовать несколько по-разному настроенных планет.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlanetParams {
+    /// Частота континентов планеты. Более высокая частота производит
+    /// более мелкие и многочисленные континенты.
+    /// Значение измеряется в радианах.
+    pub continent_frequency: f64,
+
+    /// Лакунарность континентов планеты. Изменение этого значения приводит к
+    /// немного разные континенты. Для достижения наилучших результатов это значение должно
+    /// быть случайным, но близким к 2.0.
+    pub continent_lacunarity: f64,
+
+    /// Лакунарность гор планеты. Изменение значения производит
+    /// немного другие горы. Для достижения наилучших результатов это значение должно
+    /// быть случайным, но близким к 2.0.
+    pub mountain_lacunarity: f64,
+
+    /// Лакунарность холмов планеты. Изменение этого значения приводит к
+    /// генерации других холмов. Для достижения наилучших результатов это значение должно быть
+    /// случайно, но близко к 2.0.
+    pub hills_lacunarity: f64,
+
+    /// Лакунарность равнин планеты. Изменение этого значения приводит к
+    /// генерации других равнин. Для достижения наилучших результатов это значение должно быть
+    /// случайно, но близко к 2.0.
+    pub plains_lacunarity: f64,
+
+    /// Лакунарность бесплодных земель планеты. Изменение этого значения приводит к
+    /// генерации других бесплодных земль. Для достижения наилучших результатов это значение должно
+    /// быть случайным, но близким к 2.0.
+    pub badlands_lacunarity: f64,
+
+    /// Определяет "извилистость" гор.
+    pub mountains_twist: f64,
+
+    /// Определяет «извилистость» холмов.
+    pub hills_twist: f64,
+
+    /// Определяет «извилистость» бесплодных земель.
+    pub badlands_twist: f64,
+
+    /// Определяет уровень моря на планете. Это значение должно быть между -1,0
+    /// (минимальная высота планеты) и +1.0 (максимальная высота планеты).
+    pub sea_level: f64,
+
+    /// Указывает уровень на планете, на котором появляются континентальные шельфы.
+    /// Это значение должно быть между -1,0 (минимальная высота планеты) и +1,0
+    /// (максимальная высота планеты) и должно быть меньше `sea_level`.
+    pub shelf_level: f64,
+
+    /// Определяет количество гористой местности, которая появляется на
+    /// планета. Значения варьируются от 0,0 (не включая, гор почти нет) до 1,0 (вся
+    /// местность покрыто горами). При 0,0 кривая высоты гор теряет контрольную точку. Горный рельеф будет перекрывать холмистую местность.
+    /// Поскольку местность бесплодных земель может перекрывать части горной местности
+    /// местность, установка `mountains_amount` на 1.0 может не полностью покрывать
+    /// местность в горах.
+    pub mountains_amount: f64,
+
+    /// Определяет количество холмистой местности, которая появляется на планете.
+    /// Значения варьируются от 0,0 (холмы отсутствуют) до 1,0 (вся местность покрыта
+    /// холмы). Это значение должно быть больше `mountains_amount`, иначе горы полностью
+    /// перекроют холмы. Поскольку
+    /// горный рельеф будет перекрывать части холмистой местности, а
+    /// ландшафт бесплодных земель может перекрывать части холмистой местности, устанавливая
+    /// `hills_amount` на 1.0 может не полностью покрывать холмистую местность.
+    pub hills_amount: f64,
+
+    /// Определяет количество бесплодных земель, покрывающих планету.
+    /// Значения варьируются от 0,0 (без бесплодных земель) до 1,0 (вся местность покрыта
+    /// бесплодные земли). Ландшафт бесплодных земель будет накладываться на любой другой тип ландшафта.
+    pub badlands_amount: f64,
+
+    /// Смещение для применения к определению типа ландшафта. Низкие значения (< 1,0)
+    /// заставляют шероховатые области появляться только на больших высотах. Высокие значения
+    /// (> 2.0) заставляют шероховатые области появляться на любой высоте.
+    /// процент грубых участков на планете не зависит от этого значения.
+    pub terrain_offset: f64,
+
+    /// Определяет количество "оледенения" в горах. Это значение
+    /// должен быть близок к 1,0 и больше 1,0.
+    pub mountain_glaciation: f64,
+
+    /// Максимальная глубина рек в планетарных единицах высоты.
+    pub river_depth: f64,
+}
+
+impl PlanetParams {
+    /// Проверка ограничений, описанных в документации к полям.
+    pub fn validate(&self) -> Result<(), WorldParamError> {
+        if !(-1.0..=1.0).contains(&self.sea_level) {
+            return Err(WorldParamError::SeaLevelOutOfRange(self.sea_level));
+        }
+
+        if !(-1.0..=1.0).contains(&self.shelf_level) {
+            return Err(WorldParamError::ShelfLevelOutOfRange(self.shelf_level));
+        }
+
+        if self.shelf_level >= self.sea_level {
+            return Err(WorldParamError::ShelfAboveSeaLevel {
+                shelf_level: self.shelf_level,
+                sea_level: self.sea_level,
+            });
+        }
+
+        if self.mountains_amount == 0.0 || !(0.0..=1.0).contains(&self.mountains_amount) {
+            return Err(WorldParamError::MountainsAmountOutOfRange(
+                self.mountains_amount,
+            ));
+        }
+
+        if !(0.0..=1.0).contains(&self.hills_amount) {
+            return Err(WorldParamError::HillsAmountOutOfRange(self.hills_amount));
+        }
+
+        if !(0.0..=1.0).contains(&self.badlands_amount) {
+            return Err(WorldParamError::BadlandsAmountOutOfRange(
+                self.badlands_amount,
+            ));
+        }
+
+        if self.hills_amount <= self.mountains_amount {
+            return Err(WorldParamError::HillsCoveredByMountains {
+                hills_amount: self.hills_amount,
+                mountains_amount: self.mountains_amount,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Количество числовых параметров в [`PlanetParams::values`].
+    pub(super) const COUNT: usize = 17;
+
+    /// Имена параметров в том же порядке, что и [`PlanetParams::values`].
+    pub(super) const NAMES: [&'static str; Self::COUNT] = [
+        "continent_frequency",
+        "continent_lacunarity",
+        "mountain_lacunarity",
+        "hills_lacunarity",
+        "plains_lacunarity",
+        "badlands_lacunarity",
+        "mountains_twist",
+        "hills_twist",
+        "badlands_twist",
+        "sea_level",
+        "shelf_level",
+        "mountains_amount",
+        "hills_amount",
+        "badlands_amount",
+        "terrain_offset",
+        "mountain_glaciation",
+        "river_depth",
+    ];
+
+    /// Значения всех параметров в порядке объявления полей.
+    pub(super) fn values(&self) -> [f64; Self::COUNT] {
+        [
+            self.continent_frequency,
+            self.continent_lacunarity,
+            self.mountain_lacunarity,
+            self.hills_lacunarity,
+            self.plains_lacunarity,
+            self.badlands_lacunarity,
+            self.mountains_twist,
+            self.hills_twist,
+            self.badlands_twist,
+            self.sea_level,
+            self.shelf_level,
+            self.mountains_amount,
+            self.hills_amount,
+            self.badlands_amount,
+            self.terrain_offset,
+            self.mountain_glaciation,
+            self.river_depth,
+        ]
+    }
+
+    /// Изменяемые ссылки на все параметры в порядке объявления полей.
+    pub(super) fn values_mut(&mut self) -> [&mut f64; Self::COUNT] {
+        [
+            &mut self.continent_frequency,
+            &mut self.continent_lacunarity,
+            &mut self.mountain_lacunarity,
+            &mut self.hills_lacunarity,
+            &mut self.plains_lacunarity,
+            &mut self.badlands_lacunarity,
+            &mut self.mountains_twist,
+            &mut self.hills_twist,
+            &mut self.badlands_twist,
+            &mut self.sea_level,
+            &mut self.shelf_level,
+            &mut self.mountains_amount,
+            &mut self.hills_amount,
+            &mut self.badlands_amount,
+            &mut self.terrain_offset,
+            &mut self.mountain_glaciation,
+            &mut self.river_depth,
+        ]
+    }
+
+    /// Масштабирование для применения к высотам базового континента в планетарных
+    /// единицы высоты.
+    pub fn continent_height_scale(&self) -> f64 {
+        (1.0 - self.sea_level) / 4.0
+    }
+}
+
+impl Default for PlanetParams {
+    fn default() -> Self {
+        let mountains_amount = 0.48;
+
+        Self {
+            continent_frequency: 1.0,
+            continent_lacunarity: 2.208984375,
+            mountain_lacunarity: 2.142578125,
+            hills_lacunarity: 2.162109375,
+            plains_lacunarity: 2.314453125,
+            badlands_lacunarity: 2.212890625,
+            mountains_twist: 1.0,
+            hills_twist: 1.0,
+            badlands_twist: 1.0,
+            sea_level: 0.0,
+            shelf_level: -0.375,
+            mountains_amount,
+            hills_amount: (1.0 + mountains_amount) / 2.0,
+            badlands_amount: 0.3125,
+            terrain_offset: 1.0,
+            mountain_glaciation: 0.375,
+            river_depth: 0.0234375,
+        }
+    }
+}
+
+/// Ошибка проверки параметров планеты.
+/// Каждый вариант соответствует одному нарушенному ограничению.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WorldParamError {
+    /// `sea_level` вне диапазона [-1.0, 1.0].
+    SeaLevelOutOfRange(f64),
+
+    /// `shelf_level` вне диапазона [-1.0, 1.0].
+    ShelfLevelOutOfRange(f64),
+
+    /// `shelf_level` не ниже `sea_level`.
+    ShelfAboveSeaLevel { shelf_level: f64, sea_level: f64 },
+
+    /// `mountains_amount` вне диапазона (0.0, 1.0].
+    MountainsAmountOutOfRange(f64),
+
+    /// `hills_amount` вне диапазона [0.0, 1.0].
+    HillsAmountOutOfRange(f64),
+
+    /// `badlands_amount` вне диапазона [0.0, 1.0].
+    BadlandsAmountOutOfRange(f64),
+
+    /// `hills_amount` не больше `mountains_amount`, горы полностью перекрывают холмы.
+    HillsCoveredByMountains {
+        hills_amount: f64,
+        mountains_amount: f64,
+    },
+
+    /// При этих параметрах у кривой или террасы шумового графа совпадают
+    /// контрольные точки, и различных точек меньше, чем нужно модулю.
+    TooFewGraphPoints { required: usize, points: usize },
+}
+
+impl fmt::Display for WorldParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SeaLevelOutOfRange(value) => {
+                write!(f, "sea level {} is outside [-1, 1]", value)
+            }
+            Self::ShelfLevelOutOfRange(value) => {
+                write!(f, "shelf level {} is outside [-1, 1]", value)
+            }
+            Self::ShelfAboveSeaLevel {
+                shelf_level,
+                sea_level,
+            } => write!(
+                f,
+                "shelf level {} must be below sea level {}",
+                shelf_level, sea_level
+            ),
+            Self::MountainsAmountOutOfRange(value) => {
+                write!(f, "mountains amount {} is outside (0, 1]", value)
+            }
+            Self::HillsAmountOutOfRange(value) => {
+                write!(f, "hills amount {} is outside [0, 1]", value)
+            }
+            Self::BadlandsAmountOutOfRange(value) => {
+                write!(f, "badlands amount {} is outside [0, 1]", value)
+            }
+            Self::HillsCoveredByMountains {
+                hills_amount,
+                mountains_amount,
+            } => write!(
+                f,
+                "hills amount {} must be above mountains amount {}",
+                hills_amount, mountains_amount
+            ),
+            Self::TooFewGraphPoints { required, points } => write!(
+                f,
+                "noise graph has {} distinct control points with these params, at least {} required",
+                points, required
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WorldParamError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(change: impl FnOnce(&mut PlanetParams)) -> Result<(), WorldParamError> {
+        let mut params = PlanetParams::default();
+        change(&mut params);
+        params.validate()
+    }
+
+    #[test]
+    fn default_params_are_valid() {
+        assert_eq!(PlanetParams::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn sea_level_out_of_range() {
+        assert_eq!(
+            validate(|params| params.sea_level = 1.5),
+            Err(WorldParamError::SeaLevelOutOfRange(1.5))
+        );
+        assert!(matches!(
+            validate(|params| params.sea_level = f64::NAN),
+            Err(WorldParamError::SeaLevelOutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn shelf_level_out_of_range() {
+        assert_eq!(
+            validate(|params| params.shelf_level = -1.25),
+            Err(WorldParamError::ShelfLevelOutOfRange(-1.25))
+        );
+    }
+
+    #[test]
+    fn shelf_above_sea_level() {
+        assert_eq!(
+            validate(|params| params.shelf_level = 0.0),
+            Err(WorldParamError::ShelfAboveSeaLevel {
+                shelf_level: 0.0,
+                sea_level: 0.0,
+            })
+        );
+        assert_eq!(
+            validate(|params| params.shelf_level = 0.5),
+            Err(WorldParamError::ShelfAboveSeaLevel {
+                shelf_level: 0.5,
+                sea_level: 0.0,
+            })
+        );
+    }
+
+    #[test]
+    fn mountains_amount_out_of_range() {
+        for amount in [-0.1, 0.0, 1.1] {
+            assert_eq!(
+                validate(|params| params.mountains_amount = amount),
+                Err(WorldParamError::MountainsAmountOutOfRange(amount))
+            );
+        }
+    }
+
+    #[test]
+    fn hills_amount_out_of_range() {
+        assert_eq!(
+            validate(|params| params.hills_amount = 1.1),
+            Err(WorldParamError::HillsAmountOutOfRange(1.1))
+        );
+    }
+
+    #[test]
+    fn badlands_amount_out_of_range() {
+        for amount in [-0.1, 1.1] {
+            assert_eq!(
+                validate(|params| params.badlands_amount = amount),
+                Err(WorldParamError::BadlandsAmountOutOfRange(amount))
+            );
+        }
+    }
+
+    #[test]
+    fn hills_covered_by_mountains() {
+        assert_eq!(
+            validate(|params| params.hills_amount = params.mountains_amount),
+            Err(WorldParamError::HillsCoveredByMountains {
+                hills_amount: 0.48,
+                mountains_amount: 0.48,
+            })
+        );
+    }
+
+    /// Наименьшее допустимое количество гор не ломает кривую высоты гор.
+    #[test]
+    fn small_mountains_amount_builds() {
+        let params = PlanetParams {
+            mountains_amount: 0.001,
+            ..PlanetParams::default()
+        };
+        let planet = crate::resource::world::WorldBuilder::new()
+            .set_params(params)
+            .build()
+            .unwrap();
+
+        assert!(planet.elevation(10.0, 20.0).is_finite());
+    }
+}
@@ -0,0 +1,148 @@
+//! Модуль планеты.
+//!
+//! Модули шумового графа библиотеки `noise` ссылаются друг на друга, поэтому граф
+//! не может просто вернуться из функции, которая его строит. Все модули графа
+//! размещаются в [`NoiseGraph`], а [`Planet`] владеет этим хранилищем вместе со
+//! ссылками на нужные ей выходы графа.
+
+use std::any::Any;
+
+use noise::NoiseFn;
+use strum::EnumCount;
+
+use crate::resource::world::{GeoBounds, Heightmap, PlanetParams, TerrainLayer};
+
+/// Хранилище модулей шумового графа.
+///
+/// Каждый модуль размещается в куче и не перемещается до уничтожения хранилища,
+/// поэтому ссылки на него остаются действительными всё время жизни хранилища.
+pub(super) struct NoiseGraph {
+    nodes: Vec<*mut dyn Any>,
+}
+
+impl NoiseGraph {
+    pub(super) fn new() -> Self {
+        Self { nodes: Vec::new() }
+    }
+
+    /// Размещение модуля в хранилище.
+    ///
+    /// # Safety
+    ///
+    /// Возвращаемая ссылка действительна только до уничтожения хранилища, хотя
+    /// ее время жизни `'static`. Вызывающий обязан не использовать ее после этого:
+    /// ссылка может попасть только в модули этого же хранилища или в [`Planet`]
+    /// вместе с самим хранилищем.
+    pub(super) unsafe fn add<T: 'static>(&mut self, node: T) -> &'static T {
+        let node = Box::into_raw(Box::new(node));
+        self.nodes.push(node);
+
+        // Модуль размещен в куче и освобождается только в `Drop`.
+        &*node
+    }
+}
+
+impl Drop for NoiseGraph {
+    fn drop(&mut self) {
+        // Модули уничтожаются в обратном порядке: сначала потребители, затем источники.
+        while let Some(node) = self.nodes.pop() {
+            // SAFETY: указатель получен из `Box::into_raw` и освобождается ровно один раз.
+            unsafe { drop(Box::from_raw(node)) };
+        }
+    }
+}
+
+/// Сгенерированная планета.
+///
+/// Владеет полным шумовым графом и отвечает на запросы высоты в планетарных
+/// единицах высоты: -1.0 для самых глубоких океанических впадин и +1.0 для самых
+/// высоких горных вершин.
+pub struct Planet {
+    params: PlanetParams,
+    layers: [&'static dyn NoiseFn<[f64; 3]>; TerrainLayer::COUNT],
+
+    // Должно быть объявлено последним: ссылки выше указывают внутрь хранилища.
+    _graph: NoiseGraph,
+}
+
+impl Planet {
+    pub(super) fn new(
+        params: PlanetParams,
+        graph: NoiseGraph,
+        layers: [&'static dyn NoiseFn<[f64; 3]>; TerrainLayer::COUNT],
+    ) -> Self {
+        Self {
+            params,
+            layers,
+            _graph: graph,
+        }
+    }
+
+    /// Параметры, с которыми построена планета.
+    pub fn params(&self) -> &PlanetParams {
+        &self.params
+    }
+
+    /// Высота планеты в точке с широтой `lat` и долготой `lon` (в градусах).
+    pub fn elevation(&self, lat: f64, lon: f64) -> f64 {
+        self.sample_layer(TerrainLayer::Elevation, lat, lon)
+    }
+
+    /// Значение слоя `layer` в точке с широтой `lat` и долготой `lon` (в градусах).
+    pub fn sample_layer(&self, layer: TerrainLayer, lat: f64, lon: f64) -> f64 {
+        self.layers[layer as usize].get(lat_lon_to_point(lat, lon))
+    }
+
+    /// Карта высот всей планеты размером `width` x `height` пикселей.
+    pub fn render_heightmap(&self, width: usize, height: usize) -> Heightmap {
+        self.render_heightmap_bounds(width, height, GeoBounds::WORLD)
+    }
+
+    /// Карта высот участка планеты в границах `bounds`.
+    pub fn render_heightmap_bounds(
+        &self,
+        width: usize,
+        height: usize,
+        bounds: GeoBounds,
+    ) -> Heightmap {
+        Heightmap::sample(width, height, bounds, |lat, lon| self.elevation(lat, lon))
+    }
+
+    /// Карта высот всей планеты, как в [`Planet::render_heightmap`].
+    /// После каждой строки вызывает `progress` с долей готовых строк от 0.0 до 1.0.
+    pub fn render_heightmap_with_progress(
+        &self,
+        width: usize,
+        height: usize,
+        mut progress: impl FnMut(f32),
+    ) -> Heightmap {
+        let mut pixels = 0;
+
+        Heightmap::sample(width, height, GeoBounds::WORLD, |lat, lon| {
+            let elevation = self.elevation(lat, lon);
+
+            pixels += 1;
+            if pixels % width == 0 {
+                progress((pixels / width) as f32 / height as f32);
+            }
+
+            elevation
+        })
+    }
+
+    /// Карта значений слоя `layer` по всей планете размером `width` x `height` пикселей.
+    pub fn render_layer(&self, layer: TerrainLayer, width: usize, height: usize) -> Heightmap {
+        Heightmap::sample(width, height, GeoBounds::WORLD, |lat, lon| {
+            self.sample_layer(layer, lat, lon)
+        })
+    }
+}
+
+/// Перевод широты и долготы (в градусах) в точку на единичной сфере.
+/// Повторяет преобразование `LatLonToXYZ` из libnoise.
+pub(super) fn lat_lon_to_point(lat: f64, lon: f64) -> [f64; 3] {
+    let (lat, lon) = (lat.to_radians(), lon.to_radians());
+    let r = lat.cos();
+
+    [r * lon.cos(), lat.sin(), r * lon.sin()]
+}
@@ -0,0 +1,311 @@
+//! Модуль пресетов параметров планеты.
+//!
+//! Пресет — TOML файл с частью полей [`PlanetParams`]. Пресет может
+//! унаследовать остальные поля от другого пресета через ключ `inherits`:
+//!
+//! ```toml
+//! inherits = "earthlike"
+//! sea_level = 0.25
+//! ```
+//!
+//! Поля, не заданные ни в пресете, ни в его предках, берутся из
+//! [`PlanetParams::default`]. Встроенные пресеты лежат в `assets/worldgen/presets`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::resource::world::PlanetParams;
+
+/// Встроенные пресеты: имя и содержимое файла.
+const BUILTIN_PRESETS: [(&str, &str); 5] = [
+    (
+        "earthlike",
+        include_str!("../../../assets/worldgen/presets/earthlike.toml"),
+    ),
+    (
+        "archipelago",
+        include_str!("../../../assets/worldgen/presets/archipelago.toml"),
+    ),
+    (
+        "pangaea",
+        include_str!("../../../assets/worldgen/presets/pangaea.toml"),
+    ),
+    (
+        "desert_world",
+        include_str!("../../../assets/worldgen/presets/desert_world.toml"),
+    ),
+    (
+        "alpine",
+        include_str!("../../../assets/worldgen/presets/alpine.toml"),
+    ),
+];
+
+/// Содержимое файла пресета.
+#[derive(Deserialize)]
+struct PresetFile {
+    inherits: Option<String>,
+    #[serde(flatten)]
+    values: BTreeMap<String, f64>,
+}
+
+/// Пресет параметров планеты.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanetPreset {
+    inherits: Option<String>,
+    values: [Option<f64>; PlanetParams::COUNT],
+}
+
+impl PlanetPreset {
+    /// Пресет со всеми полями `params` и без родителя.
+    pub fn from_params(params: &PlanetParams) -> Self {
+        Self {
+            inherits: None,
+            values: params.values().map(Some),
+        }
+    }
+
+    /// Разбор пресета из текста TOML.
+    pub fn from_toml(text: &str) -> Result<Self, PresetError> {
+        let file: PresetFile = toml::from_str(text).map_err(PresetError::Parse)?;
+
+        let mut values = [None; PlanetParams::COUNT];
+        for (name, value) in file.values {
+            let index = PlanetParams::NAMES
+                .iter()
+                .position(|param| *param == name)
+                .ok_or(PresetError::UnknownParameter(name))?;
+            values[index] = Some(value);
+        }
+
+        Ok(Self {
+            inherits: file.inherits,
+            values,
+        })
+    }
+
+    /// Запись пресета в TOML. Поля идут в порядке объявления в [`PlanetParams`].
+    /// Значения записываются сериализатором `toml`, поэтому `NaN` и бесконечности
+    /// становятся `nan` и `inf`, которые [`PlanetPreset::from_toml`] читает обратно.
+    pub fn to_toml(&self) -> String {
+        let mut text = String::new();
+
+        if let Some(inherits) = &self.inherits {
+            text += &format!("inherits = {}\n", toml::Value::String(inherits.clone()));
+        }
+
+        for (name, value) in PlanetParams::NAMES.iter().zip(self.values) {
+            if let Some(value) = value {
+                text += &format!("{} = {}\n", name, toml::Value::Float(value));
+            }
+        }
+
+        text
+    }
+
+    /// Загрузка пресета из файла.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PresetError> {
+        Self::from_toml(&fs::read_to_string(path).map_err(PresetError::Io)?)
+    }
+
+    /// Сохранение пресета в файл.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_toml())
+    }
+
+    /// Имя пресета, от которого наследуются незаданные поля.
+    pub fn inherits(&self) -> Option<&str> {
+        self.inherits.as_deref()
+    }
+
+    /// Функция позволяющая указать родительский пресет.
+    pub fn set_inherits(mut self, inherits: impl Into<String>) -> Self {
+        self.inherits = Some(inherits.into());
+        self
+    }
+
+    /// Наложение заданных в пресете полей на `params`.
+    fn apply(&self, params: &mut PlanetParams) {
+        for (field, value) in params.values_mut().into_iter().zip(self.values) {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+    }
+}
+
+/// Набор именованных пресетов.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresetLibrary {
+    presets: BTreeMap<String, PlanetPreset>,
+}
+
+impl PresetLibrary {
+    /// Пустой набор пресетов.
+    pub fn new() -> Self {
+        Self {
+            presets: BTreeMap::new(),
+        }
+    }
+
+    /// Встроенные пресеты: `earthlike`, `archipelago`, `pangaea`, `desert_world`, `alpine`.
+    pub fn builtin() -> Self {
+        let mut library = Self::new();
+
+        for (name, text) in BUILTIN_PRESETS {
+            let preset = PlanetPreset::from_toml(text).expect("built-in preset is invalid");
+            library.insert(name, preset);
+        }
+
+        library
+    }
+
+    /// Добавление пресета. Пресет с тем же именем заменяется.
+    pub fn insert(&mut self, name: impl Into<String>, preset: PlanetPreset) {
+        self.presets.insert(name.into(), preset);
+    }
+
+    /// Загрузка пресета из файла под именем файла без расширения.
+    /// Возвращает имя загруженного пресета.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<String, PresetError> {
+        let path = path.as_ref();
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        self.insert(name.clone(), PlanetPreset::load(path)?);
+        Ok(name)
+    }
+
+    /// Пресет с именем `name`.
+    pub fn get(&self, name: &str) -> Option<&PlanetPreset> {
+        self.presets.get(name)
+    }
+
+    /// Имена всех пресетов в алфавитном порядке.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.presets.keys().map(String::as_str)
+    }
+
+    /// Параметры планеты по пресету `name` с учетом всей цепочки наследования.
+    pub fn resolve(&self, name: &str) -> Result<PlanetParams, PresetError> {
+        let mut chain = Vec::new();
+        let mut next = Some(name);
+
+        while let Some(name) = next {
+            if chain.iter().any(|(visited, _)| *visited == name) {
+                return Err(PresetError::InheritanceCycle(name.to_string()));
+            }
+
+            let preset = self
+                .get(name)
+                .ok_or_else(|| PresetError::UnknownPreset(name.to_string()))?;
+            chain.push((name, preset));
+            next = preset.inherits();
+        }
+
+        let mut params = PlanetParams::default();
+        for (_, preset) in chain.iter().rev() {
+            preset.apply(&mut params);
+        }
+
+        Ok(params)
+    }
+}
+
+impl Default for PresetLibrary {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+/// Ошибка загрузки пресета.
+#[derive(Debug)]
+pub enum PresetError {
+    /// Файл пресета не удалось прочитать.
+    Io(io::Error),
+
+    /// Файл пресета не является корректным TOML.
+    Parse(toml::de::Error),
+
+    /// В пресете есть поле, которого нет в [`PlanetParams`].
+    UnknownParameter(String),
+
+    /// Пресета с таким именем нет в наборе.
+    UnknownPreset(String),
+
+    /// Пресет прямо или через других предков наследуется сам от себя.
+    InheritanceCycle(String),
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "failed to read planet preset: {}", error),
+            Self::Parse(error) => write!(f, "invalid planet preset: {}", error),
+            Self::UnknownParameter(name) => write!(f, "unknown planet parameter {:?}", name),
+            Self::UnknownPreset(name) => write!(f, "unknown planet preset {:?}", name),
+            Self::InheritanceCycle(name) => {
+                write!(f, "planet preset {:?} inherits from itself", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PresetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Parse(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_toml_round_trip() {
+        let params = PlanetParams {
+            sea_level: f64::NAN,
+            continent_frequency: f64::INFINITY,
+            mountains_twist: f64::NEG_INFINITY,
+            hills_twist: 0.1,
+            ..PlanetParams::default()
+        };
+        let preset = PlanetPreset::from_params(&params).set_inherits("say \"earthlike\"\n");
+
+        let text = preset.to_toml();
+        let loaded = PlanetPreset::from_toml(&text).unwrap();
+
+        assert_eq!(loaded.inherits(), preset.inherits());
+        for (loaded, value) in loaded.values.iter().zip(preset.values) {
+            assert_eq!(
+                loaded.map(f64::to_bits),
+                value.map(f64::to_bits),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn builtin_presets_round_trip() {
+        for (name, text) in BUILTIN_PRESETS {
+            let preset = PlanetPreset::from_toml(text).unwrap();
+            assert_eq!(
+                PlanetPreset::from_toml(&preset.to_toml()).unwrap(),
+                preset,
+                "{}",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,472 @@
+//! Модуль картографических проекций.
+//!
+//! Проекция переводит широту и долготу в градусах в координаты карты и
+//! обратно. Координаты карты нормированы: `x` от 0.0 (левый край) до 1.0
+//! (правый край), `y` от 0.0 (верхний край) до 1.0 (нижний край), так что
+//! одна проекция подходит картам любого размера. Пропорции карты, при которых
+//! она не искажена, возвращает [`MapProjection::aspect_ratio`].
+
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI, SQRT_2};
+
+use crate::resource::world::GeoBounds;
+
+/// Проекция поверхности планеты на плоскость.
+pub trait MapProjection {
+    /// Координаты карты точки (`lat`, `lon`).
+    /// `None`, если точка на карту не попадает.
+    fn project(&self, lat: f64, lon: f64) -> Option<(f64, f64)>;
+
+    /// Широта и долгота точки карты (`x`, `y`).
+    /// `None`, если точка лежит вне изображения планеты.
+    fn unproject(&self, x: f64, y: f64) -> Option<(f64, f64)>;
+
+    /// Отношение ширины карты к высоте.
+    fn aspect_ratio(&self) -> f64;
+
+    /// Широта и долгота точки (`x`, `y`) на карте размером `width` x `height` пикселей,
+    /// например, места щелчка мышью.
+    fn unproject_pixel(&self, width: usize, height: usize, x: f64, y: f64) -> Option<(f64, f64)> {
+        self.unproject(x / width as f64, y / height as f64)
+    }
+}
+
+/// Равнопромежуточная проекция участка `bounds`.
+/// Совпадает с сеткой [`Heightmap`](crate::resource::world::Heightmap) с теми же границами.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Equirectangular {
+    bounds: GeoBounds,
+}
+
+impl Equirectangular {
+    pub fn new(bounds: GeoBounds) -> Self {
+        Self { bounds }
+    }
+
+    pub fn bounds(&self) -> GeoBounds {
+        self.bounds
+    }
+}
+
+impl Default for Equirectangular {
+    fn default() -> Self {
+        Self::new(GeoBounds::WORLD)
+    }
+}
+
+impl MapProjection for Equirectangular {
+    fn project(&self, lat: f64, lon: f64) -> Option<(f64, f64)> {
+        let bounds = &self.bounds;
+        let east = (lon - bounds.west).rem_euclid(360.0);
+
+        (east <= bounds.lon_extent() && (bounds.south..=bounds.north).contains(&lat)).then(|| {
+            (
+                east / bounds.lon_extent(),
+                (bounds.north - lat) / bounds.lat_extent(),
+            )
+        })
+    }
+
+    fn unproject(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        inside_unit(x, y).then(|| {
+            (
+                self.bounds.north - y * self.bounds.lat_extent(),
+                wrap_lon(self.bounds.west + x * self.bounds.lon_extent()),
+            )
+        })
+    }
+
+    fn aspect_ratio(&self) -> f64 {
+        self.bounds.lon_extent() / self.bounds.lat_extent()
+    }
+}
+
+/// Проекция Меркатора. Сохраняет углы, но полюса уходят в бесконечность,
+/// поэтому карта обрезается на широте `max_lat`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mercator {
+    central_meridian: f64,
+    max_lat: f64,
+}
+
+impl Mercator {
+    /// Карта с центром на нулевом меридиане, обрезанная на широте ±85.05°,
+    /// при которой карта мира квадратная.
+    pub fn new() -> Self {
+        Self {
+            central_meridian: 0.0,
+            max_lat: 85.051_128_779_806_6,
+        }
+    }
+
+    /// Функция позволяющая указать долготу центра карты.
+    pub fn set_central_meridian(mut self, central_meridian: f64) -> Self {
+        self.central_meridian = central_meridian;
+        self
+    }
+
+    /// Функция позволяющая указать широту, на которой обрезается карта.
+    pub fn set_max_lat(mut self, max_lat: f64) -> Self {
+        self.max_lat = max_lat.clamp(1.0, 89.9);
+        self
+    }
+
+    pub fn central_meridian(&self) -> f64 {
+        self.central_meridian
+    }
+
+    pub fn max_lat(&self) -> f64 {
+        self.max_lat
+    }
+
+    /// Половина высоты карты в радианах долготы.
+    fn max_y(&self) -> f64 {
+        mercator_y(self.max_lat.to_radians())
+    }
+}
+
+impl Default for Mercator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MapProjection for Mercator {
+    fn project(&self, lat: f64, lon: f64) -> Option<(f64, f64)> {
+        (lat.abs() <= self.max_lat).then(|| {
+            let y = mercator_y(lat.to_radians());
+            (
+                lon_fraction(lon, self.central_meridian),
+                (0.5 - y / (2.0 * self.max_y())).clamp(0.0, 1.0),
+            )
+        })
+    }
+
+    fn unproject(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        inside_unit(x, y).then(|| {
+            let y = (0.5 - y) * 2.0 * self.max_y();
+            (
+                (2.0 * y.exp().atan() - FRAC_PI_2).to_degrees(),
+                wrap_lon(self.central_meridian + x * 360.0 - 180.0),
+            )
+        })
+    }
+
+    fn aspect_ratio(&self) -> f64 {
+        PI / self.max_y()
+    }
+}
+
+/// Равновеликая проекция Мольвейде: вся планета в эллипсе 2:1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mollweide {
+    central_meridian: f64,
+}
+
+impl Mollweide {
+    pub fn new() -> Self {
+        Self {
+            central_meridian: 0.0,
+        }
+    }
+
+    /// Функция позволяющая указать долготу центра карты.
+    pub fn set_central_meridian(mut self, central_meridian: f64) -> Self {
+        self.central_meridian = central_meridian;
+        self
+    }
+
+    pub fn central_meridian(&self) -> f64 {
+        self.central_meridian
+    }
+}
+
+impl Default for Mollweide {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MapProjection for Mollweide {
+    fn project(&self, lat: f64, lon: f64) -> Option<(f64, f64)> {
+        if !(-90.0..=90.0).contains(&lat) {
+            return None;
+        }
+
+        let theta = mollweide_theta(lat.to_radians());
+        let lon = (lon_fraction(lon, self.central_meridian) * 2.0 - 1.0) * PI;
+
+        // Эллипс с полуосями 2√2 и √2 сжимается в квадрат [0, 1]^2
+        let x = 2.0 * SQRT_2 / PI * lon * theta.cos();
+        let y = SQRT_2 * theta.sin();
+        Some((0.5 + x / (4.0 * SQRT_2), 0.5 - y / (2.0 * SQRT_2)))
+    }
+
+    fn unproject(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        let x = (x - 0.5) * 4.0 * SQRT_2;
+        let y = (0.5 - y) * 2.0 * SQRT_2;
+
+        if (x / (2.0 * SQRT_2)).powi(2) + (y / SQRT_2).powi(2) > 1.0 + 1e-12 {
+            return None;
+        }
+
+        let theta = (y / SQRT_2).clamp(-1.0, 1.0).asin();
+        let lat = ((2.0 * theta + (2.0 * theta).sin()) / PI)
+            .clamp(-1.0, 1.0)
+            .asin();
+
+        // На полюсах эллипс сходится в точку, и долгота не определена
+        let lon = if FRAC_PI_2 - theta.abs() < 1e-9 {
+            0.0
+        } else {
+            (PI * x / (2.0 * SQRT_2 * theta.cos())).clamp(-PI, PI)
+        };
+
+        Some((
+            lat.to_degrees(),
+            wrap_lon(self.central_meridian + lon.to_degrees()),
+        ))
+    }
+
+    fn aspect_ratio(&self) -> f64 {
+        2.0
+    }
+}
+
+/// Ортографическая проекция: планета, какой ее видно из бесконечности
+/// над точкой (`center_lat`, `center_lon`). Обратная сторона на карту не попадает.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Orthographic {
+    center_lat: f64,
+    center_lon: f64,
+}
+
+impl Orthographic {
+    pub fn new(center_lat: f64, center_lon: f64) -> Self {
+        Self {
+            center_lat: center_lat.clamp(-90.0, 90.0),
+            center_lon,
+        }
+    }
+
+    pub fn center_lat(&self) -> f64 {
+        self.center_lat
+    }
+
+    pub fn center_lon(&self) -> f64 {
+        self.center_lon
+    }
+}
+
+impl Default for Orthographic {
+    fn default() -> Self {
+        Self::new(0.0, 0.0)
+    }
+}
+
+impl MapProjection for Orthographic {
+    fn project(&self, lat: f64, lon: f64) -> Option<(f64, f64)> {
+        let (lat, lon) = (lat.to_radians(), (lon - self.center_lon).to_radians());
+        let center = self.center_lat.to_radians();
+
+        // Косинус углового расстояния до центра: отрицательный на обратной стороне
+        let cos_c = center.sin() * lat.sin() + center.cos() * lat.cos() * lon.cos();
+        if cos_c < 0.0 {
+            return None;
+        }
+
+        let x = lat.cos() * lon.sin();
+        let y = center.cos() * lat.sin() - center.sin() * lat.cos() * lon.cos();
+        Some((0.5 + x / 2.0, 0.5 - y / 2.0))
+    }
+
+    fn unproject(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        let (x, y) = (x * 2.0 - 1.0, 1.0 - y * 2.0);
+        let rho = x.hypot(y);
+        if rho > 1.0 {
+            return None;
+        }
+        if rho == 0.0 {
+            return Some((self.center_lat, wrap_lon(self.center_lon)));
+        }
+
+        let center = self.center_lat.to_radians();
+        let (sin_c, cos_c) = (rho, (1.0 - rho * rho).sqrt());
+
+        let lat = (cos_c * center.sin() + y * sin_c * center.cos() / rho).asin();
+        let lon = (x * sin_c).atan2(rho * cos_c * center.cos() - y * sin_c * center.sin());
+        Some((
+            lat.to_degrees(),
+            wrap_lon(self.center_lon + lon.to_degrees()),
+        ))
+    }
+
+    fn aspect_ratio(&self) -> f64 {
+        1.0
+    }
+}
+
+/// Долгота в пределах [-180, 180).
+fn wrap_lon(lon: f64) -> f64 {
+    (lon + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Доля ширины карты мира с центром на меридиане `central_meridian`.
+fn lon_fraction(lon: f64, central_meridian: f64) -> f64 {
+    (lon - central_meridian + 180.0).rem_euclid(360.0) / 360.0
+}
+
+fn inside_unit(x: f64, y: f64) -> bool {
+    (0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y)
+}
+
+/// Ордината Меркатора широты `lat` в радианах.
+fn mercator_y(lat: f64) -> f64 {
+    (FRAC_PI_4 + lat / 2.0).tan().ln()
+}
+
+/// Вспомогательный угол Мольвейде: корень уравнения 2θ + sin 2θ = π sin φ.
+fn mollweide_theta(lat: f64) -> f64 {
+    // У полюсов метод Ньютона сходится медленно, а ответ известен
+    if FRAC_PI_2 - lat.abs() < 1e-9 {
+        return lat;
+    }
+
+    let target = PI * lat.sin();
+    let mut theta = lat;
+    for _ in 0..50 {
+        let delta =
+            (2.0 * theta + (2.0 * theta).sin() - target) / (2.0 + 2.0 * (2.0 * theta).cos());
+        theta -= delta;
+        if delta.abs() < 1e-12 {
+            break;
+        }
+    }
+
+    theta
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Узлы сетки широт и долгот, не считая полюсов и антимеридиана.
+    fn grid() -> impl Iterator<Item = (f64, f64)> {
+        (-8..=8).flat_map(|lat| (-17..=17).map(move |lon| (lat as f64 * 10.0, lon as f64 * 10.0)))
+    }
+
+    fn assert_same_point((lat, lon): (f64, f64), (expected_lat, expected_lon): (f64, f64)) {
+        assert!(
+            (lat - expected_lat).abs() < 1e-6,
+            "latitude {} != {}",
+            lat,
+            expected_lat
+        );
+        assert!(
+            wrap_lon(lon - expected_lon).abs() < 1e-6,
+            "longitude {} != {}",
+            lon,
+            expected_lon
+        );
+    }
+
+    /// Каждая точка сетки, попавшая на карту, возвращается на свое место.
+    fn assert_round_trip(projection: &impl MapProjection) -> usize {
+        let mut projected = 0;
+        for (lat, lon) in grid() {
+            if let Some((x, y)) = projection.project(lat, lon) {
+                assert!(
+                    inside_unit(x, y),
+                    "({}, {}) maps to ({}, {})",
+                    lat,
+                    lon,
+                    x,
+                    y
+                );
+                let point = projection
+                    .unproject(x, y)
+                    .unwrap_or_else(|| panic!("({}, {}) is not unprojected", lat, lon));
+                assert_same_point(point, (lat, lon));
+                projected += 1;
+            }
+        }
+
+        projected
+    }
+
+    #[test]
+    fn equirectangular_round_trip() {
+        let count = grid().count();
+        assert_eq!(assert_round_trip(&Equirectangular::default()), count);
+
+        let bounds = GeoBounds::new(-30.0, 60.0, 100.0, 220.0);
+        assert!(assert_round_trip(&Equirectangular::new(bounds)) > 0);
+        assert_eq!(Equirectangular::new(bounds).project(0.0, 0.0), None);
+    }
+
+    #[test]
+    fn mercator_round_trip() {
+        let count = grid().count();
+        assert_eq!(assert_round_trip(&Mercator::new()), count);
+        assert_eq!(
+            assert_round_trip(&Mercator::new().set_central_meridian(45.0)),
+            count
+        );
+
+        let clipped = Mercator::new().set_max_lat(60.0);
+        assert!(assert_round_trip(&clipped) < count);
+        assert_eq!(clipped.project(70.0, 0.0), None);
+    }
+
+    #[test]
+    fn mollweide_round_trip() {
+        let count = grid().count();
+        assert_eq!(assert_round_trip(&Mollweide::new()), count);
+        assert_eq!(
+            assert_round_trip(&Mollweide::new().set_central_meridian(-60.0)),
+            count
+        );
+    }
+
+    #[test]
+    fn mollweide_poles_and_outside() {
+        let mollweide = Mollweide::new();
+
+        assert_same_point(mollweide.unproject(0.5, 0.0).unwrap(), (90.0, 0.0));
+        assert_same_point(mollweide.unproject(0.5, 1.0).unwrap(), (-90.0, 0.0));
+        assert_eq!(mollweide.project(90.0, 30.0), Some((0.5, 0.0)));
+
+        // Углы квадрата и точки у его краев лежат вне эллипса
+        for (x, y) in [
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (0.0, 1.0),
+            (0.51, 0.0),
+            (0.02, 0.2),
+            (0.9, 0.95),
+        ] {
+            assert_eq!(mollweide.unproject(x, y), None, "({}, {})", x, y);
+        }
+        assert!(mollweide.unproject(0.0, 0.5).is_some());
+    }
+
+    /// Центры выбраны так, чтобы узлы сетки не лежали на краю диска,
+    /// где долгота теряет точность.
+    #[test]
+    fn orthographic_round_trip() {
+        for (lat, lon) in [(5.0, 5.0), (35.0, 45.0), (-65.0, 175.0), (85.0, 5.0)] {
+            let orthographic = Orthographic::new(lat, lon);
+            let visible = assert_round_trip(&orthographic);
+            assert!(visible > 0 && visible < grid().count());
+        }
+    }
+
+    #[test]
+    fn orthographic_hides_far_side() {
+        let orthographic = Orthographic::new(30.0, 45.0);
+
+        assert_eq!(orthographic.project(-30.0, -135.0), None);
+        assert_eq!(orthographic.project(0.0, -100.0), None);
+        assert_eq!(orthographic.project(30.0, 45.0), Some((0.5, 0.5)));
+        assert_eq!(orthographic.unproject(0.0, 0.0), None);
+    }
+}
@@ -0,0 +1,479 @@
+//! Модуль цветной карты планеты.
+//!
+//! Аналог `RendererImage` из libnoise: каждому пикселю карты высот назначается
+//! цвет по градиенту. Градиент разбит на три полосы, привязанные к параметрам
+//! планеты:
+//! - глубины — от -1.0 до `shelf_level`;
+//! - шельф — от `shelf_level` до `sea_level`;
+//! - суша — от `sea_level` до +1.0, вплоть до снежных вершин.
+//!
+//! Опорные точки внутри полосы задаются долей от 0.0 (нижняя граница полосы)
+//! до 1.0 (верхняя граница), поэтому один градиент подходит планетам с разными
+//! уровнями моря и шельфа.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use crate::resource::world::{
+    Heightmap, LakeKind, LakeMap, LightSource, MapProjection, PlanetParams,
+};
+
+/// Цвет в формате RGB.
+pub type Color = [u8; 3];
+
+/// Цвет пикселей, для которых нет данных (`NaN` в карте высот
+/// или точка вне изображения планеты в проекции).
+const NODATA_COLOR: Color = [0, 0, 0];
+
+/// Цвета пресных и соленых озер по умолчанию.
+const FRESH_LAKE_COLOR: Color = [40, 100, 170];
+const SALT_LAKE_COLOR: Color = [150, 195, 200];
+
+/// Опорная точка градиента.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ColorStop {
+    position: f64,
+    color: Color,
+}
+
+/// Цветовой градиент высот планеты.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainGradient {
+    deep: Vec<ColorStop>,
+    shelf: Vec<ColorStop>,
+    land: Vec<ColorStop>,
+}
+
+impl TerrainGradient {
+    /// Пустой градиент.
+    /// Перед использованием в каждую полосу нужно добавить хотя бы одну точку.
+    pub fn new() -> Self {
+        Self {
+            deep: Vec::new(),
+            shelf: Vec::new(),
+            land: Vec::new(),
+        }
+    }
+
+    /// Добавление точки в полосу глубин, ниже `shelf_level`.
+    pub fn add_deep_stop(mut self, position: f64, color: Color) -> Self {
+        insert_stop(&mut self.deep, position, color);
+        self
+    }
+
+    /// Добавление точки в полосу шельфа, между `shelf_level` и `sea_level`.
+    pub fn add_shelf_stop(mut self, position: f64, color: Color) -> Self {
+        insert_stop(&mut self.shelf, position, color);
+        self
+    }
+
+    /// Добавление точки в полосу суши, выше `sea_level`.
+    pub fn add_land_stop(mut self, position: f64, color: Color) -> Self {
+        insert_stop(&mut self.land, position, color);
+        self
+    }
+
+    /// Цвет высоты `elevation` на планете с параметрами `params`.
+    pub fn color(&self, elevation: f64, params: &PlanetParams) -> Color {
+        if elevation.is_nan() {
+            return NODATA_COLOR;
+        }
+
+        let (stops, low, high) = if elevation < params.shelf_level {
+            (&self.deep, -1.0, params.shelf_level)
+        } else if elevation < params.sea_level {
+            (&self.shelf, params.shelf_level, params.sea_level)
+        } else {
+            (&self.land, params.sea_level, 1.0)
+        };
+
+        interpolate(stops, (elevation - low) / (high - low))
+    }
+}
+
+impl Default for TerrainGradient {
+    /// Палитра примера complexplanet из libnoise, пересчитанная на полосы
+    /// при уровнях моря и шельфа по умолчанию.
+    fn default() -> Self {
+        Self::new()
+            .add_deep_stop(0.0, [3, 29, 64])
+            .add_deep_stop(1.0, [5, 48, 105])
+            .add_shelf_stop(0.0, [5, 48, 105])
+            .add_shelf_stop(0.9, [6, 58, 127])
+            .add_shelf_stop(1.0, [14, 112, 192])
+            .add_land_stop(0.0, [70, 120, 60])
+            .add_land_stop(0.125, [110, 140, 75])
+            .add_land_stop(0.25, [160, 140, 111])
+            .add_land_stop(0.375, [184, 163, 141])
+            .add_land_stop(0.5, [255, 255, 255])
+            .add_land_stop(1.0, [255, 255, 255])
+    }
+}
+
+/// Построитель цветной карты из карты высот.
+#[derive(Debug, Clone)]
+pub struct MapRenderer {
+    params: PlanetParams,
+    gradient: TerrainGradient,
+    light: Option<LightSource>,
+    lakes: Option<LakeMap>,
+    fresh_lake_color: Color,
+    salt_lake_color: Color,
+}
+
+impl MapRenderer {
+    /// Создание построителя для планеты с параметрами `params`
+    /// и градиентом по умолчанию.
+    pub fn new(params: &PlanetParams) -> Self {
+        Self {
+            params: *params,
+            gradient: TerrainGradient::default(),
+            light: None,
+            lakes: None,
+            fresh_lake_color: FRESH_LAKE_COLOR,
+            salt_lake_color: SALT_LAKE_COLOR,
+        }
+    }
+
+    /// Функция позволяющая указать свой градиент.
+    pub fn set_gradient(mut self, gradient: TerrainGradient) -> Self {
+        self.gradient = gradient;
+        self
+    }
+
+    pub fn gradient(&self) -> &TerrainGradient {
+        &self.gradient
+    }
+
+    /// Включение отмывки рельефа источником света `light`.
+    pub fn enable_light(mut self, light: LightSource) -> Self {
+        self.light = Some(light);
+        self
+    }
+
+    pub fn disable_light(mut self) -> Self {
+        self.light = None;
+        self
+    }
+
+    pub fn light(&self) -> Option<&LightSource> {
+        self.light.as_ref()
+    }
+
+    /// Функция позволяющая показать на карте озера `lakes`.
+    /// Озера должны быть найдены по той же карте высот, что рисуется,
+    /// иначе построение карты вернет [`RenderError::LakeMapMismatch`].
+    pub fn set_lakes(mut self, lakes: LakeMap) -> Self {
+        self.lakes = Some(lakes);
+        self
+    }
+
+    /// Функция позволяющая указать цвет озер вида `kind`.
+    pub fn set_lake_color(mut self, kind: LakeKind, color: Color) -> Self {
+        match kind {
+            LakeKind::Fresh => self.fresh_lake_color = color,
+            LakeKind::Salt => self.salt_lake_color = color,
+        }
+        self
+    }
+
+    pub fn lakes(&self) -> Option<&LakeMap> {
+        self.lakes.as_ref()
+    }
+
+    pub fn lake_color(&self, kind: LakeKind) -> Color {
+        match kind {
+            LakeKind::Fresh => self.fresh_lake_color,
+            LakeKind::Salt => self.salt_lake_color,
+        }
+    }
+
+    /// Построение цветной карты того же размера, что и `heightmap`.
+    pub fn render(&self, heightmap: &Heightmap) -> Result<MapImage, RenderError> {
+        self.check_lakes(heightmap)?;
+        let mut pixels = Vec::with_capacity(heightmap.data().len());
+
+        for y in 0..heightmap.height() {
+            for x in 0..heightmap.width() {
+                pixels.push(self.pixel_color(heightmap, x, y));
+            }
+        }
+
+        Ok(MapImage::from_raw(
+            heightmap.width(),
+            heightmap.height(),
+            pixels,
+        ))
+    }
+
+    /// Построение цветной карты размером `width` x `height` в проекции `projection`.
+    ///
+    /// Цвет пикселя берется из ближайшего пикселя `heightmap`, поэтому карта высот
+    /// должна быть не грубее результата. Точки вне изображения планеты и
+    /// вне границ карты высот остаются черными.
+    pub fn render_projected<P>(
+        &self,
+        heightmap: &Heightmap,
+        projection: &P,
+        width: usize,
+        height: usize,
+    ) -> Result<MapImage, RenderError>
+    where
+        P: MapProjection + ?Sized,
+    {
+        self.check_lakes(heightmap)?;
+        let mut pixels = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let source = projection
+                    .unproject_pixel(width, height, x as f64 + 0.5, y as f64 + 0.5)
+                    .and_then(|(lat, lon)| heightmap.pixel_at(lat, lon));
+
+                pixels.push(match source {
+                    Some((x, y)) => self.pixel_color(heightmap, x, y),
+                    None => NODATA_COLOR,
+                });
+            }
+        }
+
+        Ok(MapImage::from_raw(width, height, pixels))
+    }
+
+    /// Озера должны лежать на сетке той же карты высот.
+    fn check_lakes(&self, heightmap: &Heightmap) -> Result<(), RenderError> {
+        match &self.lakes {
+            Some(lakes)
+                if (lakes.width(), lakes.height(), lakes.bounds())
+                    != (heightmap.width(), heightmap.height(), heightmap.bounds()) =>
+            {
+                Err(RenderError::LakeMapMismatch)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Цвет пикселя (`x`, `y`) карты высот с учетом освещения.
+    /// Зеркало озера ровное, поэтому озера не затеняются.
+    fn pixel_color(&self, heightmap: &Heightmap, x: usize, y: usize) -> Color {
+        if let Some(lake) = self.lakes.as_ref().and_then(|lakes| lakes.lake_at(x, y)) {
+            return self.lake_color(lake.kind);
+        }
+
+        let color = self
+            .gradient
+            .color(heightmap.get(x, y) as f64, &self.params);
+
+        match &self.light {
+            Some(light) => shade(color, light.intensity(heightmap, x, y)),
+            None => color,
+        }
+    }
+}
+
+/// Цветная карта планеты.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl MapImage {
+    pub(super) fn from_raw(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        debug_assert_eq!(pixels.len(), width * height);
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Пиксели построчно, с севера на юг.
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    /// Цвет пикселя (`x`, `y`).
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Сохранение в 8-битный RGB PNG.
+    pub fn save_png(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(self.pixels.concat().as_slice()))
+            .map_err(io::Error::other)
+    }
+}
+
+/// Ошибка построения цветной карты.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderError {
+    /// Озера найдены по карте высот другого размера или с другими границами.
+    LakeMapMismatch,
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LakeMapMismatch => write!(f, "lake map does not match the heightmap"),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+/// Умножение цвета на освещенность.
+fn shade(color: Color, intensity: f64) -> Color {
+    color.map(|channel| (f64::from(channel) * intensity).clamp(0.0, 255.0).round() as u8)
+}
+
+/// Вставка точки с сохранением порядка. Точка с уже занятой позицией заменяет старую.
+fn insert_stop(stops: &mut Vec<ColorStop>, position: f64, color: Color) {
+    let stop = ColorStop { position, color };
+
+    match stops.binary_search_by(|s| s.position.total_cmp(&position)) {
+        Ok(i) => stops[i] = stop,
+        Err(i) => stops.insert(i, stop),
+    }
+}
+
+/// Линейная интерполяция цвета в позиции `t`.
+/// За пределами крайних точек берется цвет ближайшей из них.
+fn interpolate(stops: &[ColorStop], t: f64) -> Color {
+    assert!(!stops.is_empty(), "gradient band has no color stops");
+
+    let upper = stops.partition_point(|stop| stop.position <= t);
+    if upper == 0 {
+        return stops[0].color;
+    }
+    if upper == stops.len() {
+        return stops[upper - 1].color;
+    }
+
+    let (a, b) = (stops[upper - 1], stops[upper]);
+    let alpha = (t - a.position) / (b.position - a.position);
+
+    let mut color = [0; 3];
+    for (channel, (a, b)) in color.iter_mut().zip(a.color.into_iter().zip(b.color)) {
+        *channel = (f64::from(a) + (f64::from(b) - f64::from(a)) * alpha).round() as u8;
+    }
+
+    color
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::world::{Equirectangular, GeoBounds, LakeConfig, Orthographic};
+
+    const DEEP: Color = [10, 0, 0];
+    const SHELF: Color = [0, 10, 0];
+    const LAND: Color = [0, 0, 10];
+
+    fn gradient() -> TerrainGradient {
+        TerrainGradient::new()
+            .add_deep_stop(0.0, DEEP)
+            .add_shelf_stop(0.0, SHELF)
+            .add_land_stop(0.0, LAND)
+            .add_land_stop(1.0, [0, 0, 250])
+    }
+
+    #[test]
+    fn gradient_bands() {
+        let params = PlanetParams::default();
+        let gradient = gradient();
+
+        let cases = [
+            (-1.0, DEEP),
+            (params.shelf_level - 0.01, DEEP),
+            (params.shelf_level, SHELF),
+            (params.sea_level - 0.01, SHELF),
+            (params.sea_level, LAND),
+            (0.5, [0, 0, 130]),
+            (1.0, [0, 0, 250]),
+            (f64::NAN, NODATA_COLOR),
+        ];
+        for (elevation, color) in cases {
+            assert_eq!(gradient.color(elevation, &params), color, "{}", elevation);
+        }
+    }
+
+    #[test]
+    fn stop_at_same_position_replaces_old() {
+        let gradient = gradient().add_land_stop(1.0, [0, 0, 50]);
+        assert_eq!(gradient.color(1.0, &PlanetParams::default()), [0, 0, 50]);
+    }
+
+    fn heightmap() -> Heightmap {
+        let data = (0..16 * 8).map(|i| (i % 7) as f32 / 3.0 - 1.0).collect();
+        Heightmap::from_raw(16, 8, GeoBounds::WORLD, data)
+    }
+
+    #[test]
+    fn projected_equirectangular_matches_render() {
+        let renderer = MapRenderer::new(&PlanetParams::default());
+        let heightmap = heightmap();
+
+        assert_eq!(
+            renderer
+                .render_projected(&heightmap, &Equirectangular::default(), 16, 8)
+                .unwrap(),
+            renderer.render(&heightmap).unwrap()
+        );
+    }
+
+    #[test]
+    fn projected_outside_planet_is_nodata() {
+        let renderer = MapRenderer::new(&PlanetParams::default());
+        let image = renderer
+            .render_projected(&heightmap(), &Orthographic::default(), 16, 16)
+            .unwrap();
+
+        assert_eq!(image.get(0, 0), NODATA_COLOR);
+        assert_eq!(image.get(15, 15), NODATA_COLOR);
+        assert_ne!(image.get(8, 8), NODATA_COLOR);
+    }
+
+    #[test]
+    fn mismatched_lakes_are_rejected() {
+        let params = PlanetParams::default();
+        let heightmap = heightmap();
+        let shifted = Heightmap::from_raw(
+            16,
+            8,
+            GeoBounds::new(-45.0, 45.0, -90.0, 90.0),
+            heightmap.data().to_vec(),
+        );
+
+        let renderer = MapRenderer::new(&params).set_lakes(LakeMap::detect(
+            &shifted,
+            &params,
+            &LakeConfig::default(),
+        ));
+        assert_eq!(
+            renderer.render(&heightmap),
+            Err(RenderError::LakeMapMismatch)
+        );
+        assert!(renderer.render(&shifted).is_ok());
+    }
+}
@@ -0,0 +1,236 @@
+//! Модуль параллельной выборки планеты.
+//!
+//! Модули `Cache` шумового графа запоминают последнюю точку и не могут
+//! использоваться из нескольких потоков, поэтому [`Planet`] не передается между
+//! потоками. Вместо этого каждый поток строит собственную планету из копии
+//! [`WorldBuilder`] и заполняет прямоугольные участки карты по очереди.
+//! Координаты пикселей и значения считаются так же, как в однопоточной
+//! [`Planet::render_heightmap`], поэтому результат совпадает до бита.
+//!
+//! [`Planet`]: crate::resource::world::Planet
+//! [`Planet::render_heightmap`]: crate::resource::world::Planet::render_heightmap
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::resource::world::heightmap::pixel_lat_lon;
+use crate::resource::world::{GeoBounds, Heightmap, TerrainLayer, WorldBuilder, WorldParamError};
+
+/// Размер участка по умолчанию в пикселях.
+const DEFAULT_TILE_SIZE: usize = 256;
+
+/// Многопоточный построитель карт планеты.
+#[derive(Debug, Clone)]
+pub struct ChunkedSampler {
+    builder: WorldBuilder,
+    tile_size: usize,
+    threads: usize,
+}
+
+impl ChunkedSampler {
+    /// Создание построителя для мира из `builder`.
+    /// По умолчанию используется по потоку на каждое логическое ядро.
+    pub fn new(builder: WorldBuilder) -> Self {
+        Self {
+            builder,
+            tile_size: DEFAULT_TILE_SIZE,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        }
+    }
+
+    /// Функция позволяющая указать сторону квадратного участка в пикселях.
+    pub fn set_tile_size(mut self, tile_size: usize) -> Self {
+        self.tile_size = tile_size.max(1);
+        self
+    }
+
+    /// Функция позволяющая указать число рабочих потоков.
+    pub fn set_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn tile_size(&self) -> usize {
+        self.tile_size
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Карта высот всей планеты размером `width` x `height` пикселей.
+    pub fn render_heightmap(
+        &self,
+        width: usize,
+        height: usize,
+    ) -> Result<Heightmap, WorldParamError> {
+        self.render_heightmap_with_progress(width, height, |_| {})
+    }
+
+    /// Карта высот всей планеты. После каждого готового участка вызывает
+    /// `progress` с долей готовых участков от 0.0 до 1.0.
+    pub fn render_heightmap_with_progress(
+        &self,
+        width: usize,
+        height: usize,
+        progress: impl FnMut(f32),
+    ) -> Result<Heightmap, WorldParamError> {
+        self.sample(
+            TerrainLayer::Elevation,
+            width,
+            height,
+            GeoBounds::WORLD,
+            progress,
+        )
+    }
+
+    /// Карта высот участка планеты в границах `bounds`.
+    pub fn render_heightmap_bounds(
+        &self,
+        width: usize,
+        height: usize,
+        bounds: GeoBounds,
+    ) -> Result<Heightmap, WorldParamError> {
+        self.sample(TerrainLayer::Elevation, width, height, bounds, |_| {})
+    }
+
+    /// Карта значений слоя `layer` по всей планете размером `width` x `height` пикселей.
+    pub fn render_layer(
+        &self,
+        layer: TerrainLayer,
+        width: usize,
+        height: usize,
+    ) -> Result<Heightmap, WorldParamError> {
+        self.sample(layer, width, height, GeoBounds::WORLD, |_| {})
+    }
+
+    /// Заполнение карты по участкам. Потоки забирают участки по порядку номеров,
+    /// а готовые участки копируются в карту в вызывающем потоке.
+    fn sample(
+        &self,
+        layer: TerrainLayer,
+        width: usize,
+        height: usize,
+        bounds: GeoBounds,
+        mut progress: impl FnMut(f32),
+    ) -> Result<Heightmap, WorldParamError> {
+        self.builder.params().validate()?;
+
+        let columns = width.div_ceil(self.tile_size);
+        let tiles = columns * height.div_ceil(self.tile_size);
+        let tile_rect = |tile: usize| {
+            let (x, y) = (
+                tile % columns * self.tile_size,
+                tile / columns * self.tile_size,
+            );
+            (
+                x..(x + self.tile_size).min(width),
+                y..(y + self.tile_size).min(height),
+            )
+        };
+
+        let next_tile = AtomicUsize::new(0);
+        let mut data = vec![0.0; width * height];
+
+        thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+
+            for _ in 0..self.threads.min(tiles) {
+                let (builder, sender, next_tile) =
+                    (self.builder.clone(), sender.clone(), &next_tile);
+
+                scope.spawn(move || {
+                    let planet = builder
+                        .build()
+                        .expect("planet params are validated before sampling");
+
+                    loop {
+                        let tile = next_tile.fetch_add(1, Ordering::Relaxed);
+                        if tile >= tiles {
+                            break;
+                        }
+
+                        let (xs, ys) = tile_rect(tile);
+                        let mut values = Vec::with_capacity(xs.len() * ys.len());
+                        for y in ys {
+                            for x in xs.clone() {
+                                let (lat, lon) = pixel_lat_lon(width, height, &bounds, x, y);
+                                values.push(planet.sample_layer(layer, lat, lon) as f32);
+                            }
+                        }
+
+                        if sender.send((tile, values)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+
+            // Канал закрывается, когда все потоки закончат работу
+            drop(sender);
+
+            for (done, (tile, values)) in receiver.iter().enumerate() {
+                let (xs, ys) = tile_rect(tile);
+                for (row, y) in values.chunks(xs.len()).zip(ys) {
+                    data[y * width + xs.start..y * width + xs.end].copy_from_slice(row);
+                }
+
+                progress((done + 1) as f32 / tiles as f32);
+            }
+        });
+
+        Ok(Heightmap::from_raw(width, height, bounds, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_bit_identical(chunked: &Heightmap, expected: &Heightmap) {
+        assert_eq!(chunked.width(), expected.width());
+        assert_eq!(chunked.height(), expected.height());
+        assert_eq!(chunked.bounds(), expected.bounds());
+        for (i, (chunked, expected)) in chunked.data().iter().zip(expected.data()).enumerate() {
+            assert_eq!(chunked.to_bits(), expected.to_bits(), "pixel {}", i);
+        }
+    }
+
+    #[test]
+    fn matches_planet_render() {
+        let builder = WorldBuilder::new().set_seed(11);
+        let planet = builder.clone().build().unwrap();
+        let sampler = ChunkedSampler::new(builder).set_tile_size(8).set_threads(3);
+
+        // Размер не делится на участки, крайние участки неполные.
+        let (width, height) = (37, 19);
+        assert_bit_identical(
+            &sampler.render_heightmap(width, height).unwrap(),
+            &planet.render_heightmap(width, height),
+        );
+
+        let bounds = GeoBounds::new(-20.0, 35.0, 10.0, 80.0);
+        assert_bit_identical(
+            &sampler
+                .render_heightmap_bounds(width, height, bounds)
+                .unwrap(),
+            &planet.render_heightmap_bounds(width, height, bounds),
+        );
+    }
+
+    #[test]
+    fn progress_reaches_one() {
+        let sampler = ChunkedSampler::new(WorldBuilder::new().set_seed(11))
+            .set_tile_size(8)
+            .set_threads(2);
+
+        let mut reports = Vec::new();
+        sampler
+            .render_heightmap_with_progress(20, 10, |done| reports.push(done))
+            .unwrap();
+
+        assert_eq!(reports.len(), 6);
+        assert_eq!(reports.last(), Some(&1.0));
+    }
+}
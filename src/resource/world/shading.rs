@@ -0,0 +1,239 @@
+//! Модуль рельефного освещения.
+//!
+//! Отмывка рельефа (hillshade) повторяет `EnableLight` / `SetLightContrast`
+//! из libnoise: яркость пикселя зависит от разности высот соседних пикселей и
+//! направления на источник света. Из тех же разностей строится карта нормалей
+//! в касательном пространстве для материала трехмерного ландшафта.
+//!
+//! Если карта высот охватывает все 360° долготы, соседи на краях карты берутся
+//! с противоположного края, поэтому на линии перемены дат не появляется шов.
+
+use std::f64::consts::SQRT_2;
+
+use crate::resource::world::{Heightmap, MapImage};
+
+/// Источник света для отмывки рельефа.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightSource {
+    azimuth: f64,
+    elevation: f64,
+    contrast: f64,
+    brightness: f64,
+}
+
+impl LightSource {
+    /// Источник света по умолчанию: азимут и высота 45°, контраст 1.0 и яркость 2.0.
+    ///
+    /// В формуле libnoise ровная поверхность под светом с высоты 45° получает
+    /// освещенность 0.5, поэтому яркость 2.0 сохраняет ее исходный цвет.
+    pub fn new() -> Self {
+        Self {
+            azimuth: 45.0,
+            elevation: 45.0,
+            contrast: 1.0,
+            brightness: 2.0,
+        }
+    }
+
+    /// Азимут источника света в градусах.
+    /// 0° — свет с востока, 90° — с севера.
+    pub fn set_azimuth(mut self, azimuth: f64) -> Self {
+        self.azimuth = azimuth;
+        self
+    }
+
+    /// Высота источника света над горизонтом в градусах.
+    pub fn set_elevation(mut self, elevation: f64) -> Self {
+        self.elevation = elevation;
+        self
+    }
+
+    /// Контраст между освещенными склонами и склонами в тени.
+    ///
+    /// Множитель разности высот соседних пикселей, т.е. сила рельефа. Как и в
+    /// libnoise, подбирается под разрешение карты: чем мельче пиксель, тем
+    /// меньше разность высот и тем больше нужен контраст.
+    pub fn set_contrast(mut self, contrast: f64) -> Self {
+        self.contrast = contrast.max(0.0);
+        self
+    }
+
+    /// Общая яркость освещения.
+    pub fn set_brightness(mut self, brightness: f64) -> Self {
+        self.brightness = brightness;
+        self
+    }
+
+    pub fn azimuth(&self) -> f64 {
+        self.azimuth
+    }
+
+    pub fn elevation(&self) -> f64 {
+        self.elevation
+    }
+
+    pub fn contrast(&self) -> f64 {
+        self.contrast
+    }
+
+    pub fn brightness(&self) -> f64 {
+        self.brightness
+    }
+
+    /// Освещенность пикселя (`x`, `y`) карты высот с учетом яркости.
+    /// 0.0 соответствует полной тени.
+    pub fn intensity(&self, heightmap: &Heightmap, x: usize, y: usize) -> f64 {
+        let [left, right, north, south] = neighbours(heightmap, x, y);
+
+        let (azimuth, elevation) = (self.azimuth.to_radians(), self.elevation.to_radians());
+
+        let io = SQRT_2 * elevation.sin() / 2.0;
+        let ix = (1.0 - io) * self.contrast * SQRT_2 * elevation.cos() * azimuth.cos();
+        let iy = (1.0 - io) * self.contrast * SQRT_2 * elevation.cos() * azimuth.sin();
+
+        let intensity = ix * (left - right) + iy * (south - north) + io;
+
+        intensity.max(0.0) * self.brightness
+    }
+}
+
+impl Default for LightSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Heightmap {
+    /// Отмывка рельефа: освещенность каждого пикселя, построчно с севера на юг.
+    pub fn hillshade(&self, light: &LightSource) -> Vec<f32> {
+        (0..self.height())
+            .flat_map(|y| (0..self.width()).map(move |x| (x, y)))
+            .map(|(x, y)| light.intensity(self, x, y) as f32)
+            .collect()
+    }
+
+    /// Карта нормалей в касательном пространстве.
+    ///
+    /// Ось X направлена на восток, ось Y — на север (вверх по изображению),
+    /// как ожидают материалы Bevy. `strength` — множитель разности высот
+    /// соседних пикселей, аналог контраста у [`LightSource`].
+    pub fn normal_map(&self, strength: f64) -> MapImage {
+        let mut pixels = Vec::with_capacity(self.width() * self.height());
+
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let [left, right, north, south] = neighbours(self, x, y);
+
+                let normal = [
+                    (left - right) / 2.0 * strength,
+                    (south - north) / 2.0 * strength,
+                    1.0,
+                ];
+                let length = normal.iter().map(|n| n * n).sum::<f64>().sqrt();
+
+                pixels.push(normal.map(|n| ((n / length * 0.5 + 0.5) * 255.0).round() as u8));
+            }
+        }
+
+        MapImage::from_raw(self.width(), self.height(), pixels)
+    }
+}
+
+/// Высоты соседей пикселя: запад, восток, север, юг.
+pub(super) fn neighbours(heightmap: &Heightmap, x: usize, y: usize) -> [f64; 4] {
+    let (width, height) = (heightmap.width(), heightmap.height());
+    let wraps = wraps_around(heightmap);
+
+    let left = match x {
+        0 if wraps => width - 1,
+        0 => 0,
+        x => x - 1,
+    };
+    let right = match x + 1 {
+        r if r < width => r,
+        _ if wraps => 0,
+        _ => x,
+    };
+    let north = y.saturating_sub(1);
+    let south = (y + 1).min(height - 1);
+
+    let center = heightmap.get(x, y) as f64;
+    let value = |x, y| {
+        let value = heightmap.get(x, y) as f64;
+        if value.is_nan() {
+            center
+        } else {
+            value
+        }
+    };
+
+    [
+        value(left, y),
+        value(right, y),
+        value(x, north),
+        value(x, south),
+    ]
+}
+
+/// Карта охватывает все 360° долготы, и ее края соседствуют.
+pub(super) fn wraps_around(heightmap: &Heightmap) -> bool {
+    (heightmap.bounds().lon_extent() - 360.0).abs() < f64::EPSILON
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::world::GeoBounds;
+
+    /// Наклонная плоскость 8x8, не охватывающая всю долготу.
+    fn plane(east: f32, south: f32) -> Heightmap {
+        let data = (0..8)
+            .flat_map(|y| (0..8).map(move |x| x as f32 * east + y as f32 * south))
+            .collect();
+        Heightmap::from_raw(8, 8, GeoBounds::new(-10.0, 10.0, -10.0, 10.0), data)
+    }
+
+    #[test]
+    fn flat_map_is_uniform() {
+        let heightmap = plane(0.0, 0.0);
+
+        let shade = heightmap.hillshade(&LightSource::new());
+        assert!(shade.iter().all(|&i| (i - shade[0]).abs() < 1e-6));
+        assert!((shade[0] - 1.0).abs() < 1e-6);
+
+        let normals = heightmap.normal_map(10.0);
+        for y in 0..8 {
+            for x in 0..8 {
+                assert_eq!(normals.get(x, y), [128, 128, 255]);
+            }
+        }
+    }
+
+    #[test]
+    fn slope_facing_light_is_brighter() {
+        // Свет с востока
+        let light = LightSource::new().set_azimuth(0.0);
+
+        let facing = plane(-0.1, 0.0).hillshade(&light);
+        let away = plane(0.1, 0.0).hillshade(&light);
+        let flat = plane(0.0, 0.0).hillshade(&light);
+
+        for i in 0..facing.len() {
+            assert!(facing[i] > flat[i], "pixel {}", i);
+            assert!(flat[i] > away[i], "pixel {}", i);
+        }
+    }
+
+    #[test]
+    fn normal_axes_point_east_and_north() {
+        // Поверхность, понижающаяся к востоку, смотрит на восток
+        let [x, y, _] = plane(-0.1, 0.0).normal_map(10.0).get(4, 4);
+        assert!(x > 128);
+        assert_eq!(y, 128);
+
+        // Поверхность, понижающаяся к северу, смотрит на север
+        let [x, y, _] = plane(0.0, 0.1).normal_map(10.0).get(4, 4);
+        assert_eq!(x, 128);
+        assert!(y > 128);
+    }
+}
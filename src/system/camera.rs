@@ -26,7 +26,7 @@ lazy_static! {
 
 /// Создание сущности способную панорамировать и вращаться по орбите
 #[derive(Component)]
-struct PanOrbitCamera {
+pub struct PanOrbitCamera {
     /// «Точка фокуса», вокруг которой нужно вращаться.
     /// Автоматически обновляется при панорамировании камеры
     pub focus: Vec3,
@@ -44,6 +44,13 @@ impl Default for PanOrbitCamera {
     }
 }
 
+impl PanOrbitCamera {
+    /// Приближение камеры: 0.0 — ближе всего к фокусу, 1.0 — дальше всего.
+    pub fn zoom(&self) -> f32 {
+        ((self.radius - *MAX_ZOOM) / (*MIN_ZOOM - *MAX_ZOOM)).clamp(0.0, 1.0)
+    }
+}
+
 pub struct Camera;
 
 impl Camera {
@@ -0,0 +1,305 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use bevy::log::info;
+use bevy::prelude::{
+    App, Assets, Commands, Component, DespawnRecursiveExt, Entity, EventReader, Handle, Image,
+    Mesh, PbrBundle, Plugin, Query, Res, ResMut, StandardMaterial, SystemSet, Transform,
+};
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use crossbeam_channel::{Receiver, Sender};
+use futures_lite::future;
+use unistone::world::{ChunkId, ChunkQuadtree, LodSettings, LodUpdate, WorldBuilder};
+
+use super::camera::PanOrbitCamera;
+use super::planet_mesh::PlanetMeshBuilder;
+use super::terrain::{planet_mesh_builder, terrain_image, TerrainSettings};
+use super::worldgen::WorldGenerated;
+
+/// Настройки ландшафта с уровнями детализации.
+///
+/// Радиус и преувеличение рельефа берутся из [`TerrainSettings`].
+#[derive(Debug, Clone)]
+pub struct LodTerrainSettings {
+    pub lod: LodSettings,
+    /// Число делений ребра каждого участка.
+    pub subdivisions: usize,
+    /// Высота точки обзора над поверхностью в радиусах планеты,
+    /// когда камера приближена до предела.
+    pub near_altitude: f64,
+    /// Высота точки обзора, когда камера отдалена до предела.
+    pub far_altitude: f64,
+}
+
+impl Default for LodTerrainSettings {
+    fn default() -> Self {
+        Self {
+            lod: LodSettings::default(),
+            subdivisions: 16,
+            near_altitude: 0.05,
+            far_altitude: 1.0,
+        }
+    }
+}
+
+/// Маркер сущности с участком ландшафта.
+#[derive(Component)]
+pub struct TerrainChunk;
+
+/// Участки текущего мира.
+#[derive(Default)]
+struct LodTerrain {
+    material: Handle<StandardMaterial>,
+    quadtree: Option<ChunkQuadtree>,
+    chunks: HashMap<ChunkId, Entity>,
+    /// Участки, сетки которых еще строятся.
+    pending: HashSet<ChunkId>,
+    jobs: Option<ChunkJobs>,
+    /// Замененные участки, которые показываются, пока не готовы их замены.
+    retired: Vec<(ChunkId, Entity)>,
+}
+
+/// Построение сеток участков одного мира.
+///
+/// Как и в `ChunkedSampler`, каждая фоновая задача строит собственную планету
+/// и забирает участки из общей очереди, пока та не опустеет.
+struct ChunkJobs {
+    builder: WorldBuilder,
+    mesh_builder: PlanetMeshBuilder,
+    queue: Arc<Mutex<VecDeque<ChunkId>>>,
+    sender: Sender<(ChunkId, Mesh)>,
+    receiver: Receiver<(ChunkId, Mesh)>,
+    workers: Vec<Task<()>>,
+}
+
+impl ChunkJobs {
+    fn new(builder: WorldBuilder, mesh_builder: PlanetMeshBuilder) -> Self {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+
+        Self {
+            builder,
+            mesh_builder,
+            queue: Default::default(),
+            sender,
+            receiver,
+            workers: Vec::new(),
+        }
+    }
+
+    /// Запуск недостающих задач, если в очереди есть участки.
+    fn spawn_workers(&mut self, pool: &AsyncComputeTaskPool) {
+        self.workers
+            .retain_mut(|worker| future::block_on(future::poll_once(worker)).is_none());
+
+        let queued = self.queue.lock().unwrap().len();
+        while self.workers.len() < pool.thread_num().min(queued) {
+            let (builder, mesh_builder, queue, sender) = (
+                self.builder.clone(),
+                self.mesh_builder.clone(),
+                self.queue.clone(),
+                self.sender.clone(),
+            );
+
+            self.workers.push(pool.spawn(async move {
+                let planet = builder
+                    .build()
+                    .expect("planet params are validated by world generation");
+
+                loop {
+                    let chunk = queue.lock().unwrap().pop_front();
+                    let chunk = match chunk {
+                        Some(chunk) => chunk,
+                        None => break,
+                    };
+
+                    if sender
+                        .send((chunk, mesh_builder.build_chunk(&planet, chunk)))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }));
+        }
+    }
+}
+
+impl Drop for ChunkJobs {
+    fn drop(&mut self) {
+        // Задачи доделывают текущий участок и завершаются
+        self.queue.lock().unwrap().clear();
+    }
+}
+
+/// Ландшафт планеты из участков, которые делятся при приближении камеры.
+///
+/// Мир строит [`WorldGenPlugin`](super::worldgen::WorldGenPlugin), сетки участков
+/// строятся в фоне по мере надобности. Орбита [`PanOrbitCamera`] не подходит к
+/// планете ближе нескольких радиусов, поэтому точкой обзора служит точка над
+/// поверхностью под камерой: ее высота растет от `near_altitude` до `far_altitude`
+/// вместе с отдалением камеры.
+pub struct LodTerrainPlugin;
+
+impl LodTerrainPlugin {
+    /// Замена всех участков новым миром. Пока новые сетки строятся, видны старые.
+    fn reset(
+        pool: Res<AsyncComputeTaskPool>,
+        settings: Res<LodTerrainSettings>,
+        terrain_settings: Res<TerrainSettings>,
+        mut generated: EventReader<WorldGenerated>,
+        mut images: ResMut<Assets<Image>>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+        mut terrain: ResMut<LodTerrain>,
+    ) {
+        let world = match generated.iter().last() {
+            Some(WorldGenerated(world)) => world,
+            None => return,
+        };
+
+        let terrain = &mut *terrain;
+        terrain.material = materials.add(StandardMaterial {
+            base_color_texture: Some(images.add(terrain_image(&world.map))),
+            perceptual_roughness: 0.9,
+            ..Default::default()
+        });
+
+        let quadtree = terrain
+            .quadtree
+            .get_or_insert_with(|| ChunkQuadtree::new(settings.lod));
+        let leaves = quadtree.leaves().copied().collect();
+
+        terrain.retired.extend(terrain.chunks.drain());
+        terrain.pending.clear();
+        terrain.jobs = Some(ChunkJobs::new(
+            world.builder.clone(),
+            planet_mesh_builder(&terrain_settings, world.builder.params())
+                .set_subdivisions(settings.subdivisions),
+        ));
+
+        terrain.apply(
+            &pool,
+            LodUpdate {
+                added: leaves,
+                removed: Vec::new(),
+            },
+        );
+
+        info!("rebuilding terrain chunks");
+    }
+
+    /// Деление и слияние участков по положению камеры.
+    fn update_chunks(
+        pool: Res<AsyncComputeTaskPool>,
+        settings: Res<LodTerrainSettings>,
+        cameras: Query<(&Transform, &PanOrbitCamera)>,
+        mut terrain: ResMut<LodTerrain>,
+    ) {
+        let (transform, camera) = match cameras.get_single() {
+            Ok(camera) => camera,
+            Err(_) => return,
+        };
+        let quadtree = match terrain.quadtree.as_mut() {
+            Some(quadtree) => quadtree,
+            None => return,
+        };
+
+        let altitude = settings.near_altitude
+            + (settings.far_altitude - settings.near_altitude) * f64::from(camera.zoom());
+        let viewpoint = transform.translation.normalize_or_zero().as_dvec3() * (1.0 + altitude);
+
+        let update = quadtree.update(viewpoint.to_array());
+        if !update.is_empty() {
+            terrain.apply(&pool, update);
+        }
+    }
+
+    /// Появление готовых участков. Замененный участок исчезает, когда готовы
+    /// все пересекающиеся с ним новые, чтобы на его месте не было дыр.
+    fn poll_chunks(
+        pool: Res<AsyncComputeTaskPool>,
+        mut commands: Commands,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut terrain: ResMut<LodTerrain>,
+    ) {
+        let terrain = &mut *terrain;
+        let jobs = match terrain.jobs.as_mut() {
+            Some(jobs) => jobs,
+            None => return,
+        };
+
+        for (chunk, mesh) in jobs.receiver.try_iter() {
+            // Участок мог стать ненужным, пока строилась его сетка
+            if !terrain.pending.remove(&chunk) {
+                continue;
+            }
+
+            let entity = commands
+                .spawn_bundle(PbrBundle {
+                    mesh: meshes.add(mesh),
+                    material: terrain.material.clone(),
+                    transform: Transform::identity(),
+                    ..Default::default()
+                })
+                .insert(TerrainChunk)
+                .id();
+            terrain.chunks.insert(chunk, entity);
+        }
+
+        let pending = &terrain.pending;
+        terrain.retired.retain(|(chunk, entity)| {
+            let replaced = !pending
+                .iter()
+                .any(|new| new.contains(chunk) || chunk.contains(new));
+            if replaced {
+                commands.entity(*entity).despawn_recursive();
+            }
+            !replaced
+        });
+
+        jobs.spawn_workers(&pool);
+    }
+}
+
+impl LodTerrain {
+    /// Постановка новых участков в очередь и отмена ненужных.
+    fn apply(&mut self, pool: &AsyncComputeTaskPool, update: LodUpdate) {
+        let jobs = match self.jobs.as_mut() {
+            Some(jobs) => jobs,
+            None => return,
+        };
+
+        for chunk in &update.removed {
+            self.pending.remove(chunk);
+            self.retired
+                .extend(self.chunks.remove(chunk).map(|entity| (*chunk, entity)));
+        }
+
+        let mut queue = jobs.queue.lock().unwrap();
+        queue.retain(|chunk| self.pending.contains(chunk));
+        for chunk in update.added {
+            if self.pending.insert(chunk) {
+                queue.push_back(chunk);
+            }
+        }
+        drop(queue);
+
+        jobs.spawn_workers(pool);
+    }
+}
+
+impl Plugin for LodTerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.world
+            .get_resource_or_insert_with(TerrainSettings::default);
+
+        app.init_resource::<LodTerrainSettings>()
+            .init_resource::<LodTerrain>()
+            .add_system_set(
+                SystemSet::new()
+                    .label(super::Systems::Lod)
+                    .with_system(LodTerrainPlugin::reset)
+                    .with_system(LodTerrainPlugin::update_chunks)
+                    .with_system(LodTerrainPlugin::poll_chunks),
+            );
+    }
+}
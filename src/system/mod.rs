@@ -1,12 +1,21 @@
 pub mod camera;
+pub mod lod;
+pub mod planet_mesh;
+pub mod terrain;
+pub mod water;
 pub mod window;
+pub mod worldgen;
 
 use bevy::ecs::schedule::SystemLabel;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
 pub(super) enum Systems {
     Camera,
+    Lod,
+    Terrain,
+    Water,
     Window,
+    WorldGen,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -0,0 +1,531 @@
+use std::collections::HashMap;
+
+use bevy::math::{DVec3, Vec3};
+use bevy::prelude::Mesh;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use unistone::world::{
+    cube_to_sphere, slope_from_tilt, BiomeClassifier, BiomePalette, ChunkId, LakeKind, LakeMap,
+    Planet, PlanetParams, TerrainGradient, TerrainLayer,
+};
+
+/// Построитель сетки планеты из куба, спроецированного на сферу.
+///
+/// Каждая грань куба делится на `subdivisions` x `subdivisions` квадратов.
+/// Вершины на ребрах и углах куба общие для соседних граней, поэтому сглаженные
+/// нормали на стыках граней не имеют разрывов. Также строятся сетки отдельных
+/// участков поверхности для уровней детализации, см. [`PlanetMeshBuilder::build_chunk`].
+///
+/// Сетка содержит позиции, нормали, цвета вершин по градиенту высот или биомам и
+/// равнопромежуточные UV, совпадающие с картами [`unistone::world::MapImage`].
+/// `StandardMaterial` в Bevy 0.7 не использует цвета вершин, они нужны своим материалам.
+#[derive(Debug, Clone)]
+pub struct PlanetMeshBuilder {
+    subdivisions: usize,
+    radius: f32,
+    exaggeration: f32,
+    gradient: TerrainGradient,
+    biomes: Option<(BiomeClassifier, BiomePalette)>,
+}
+
+impl Default for PlanetMeshBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PlanetMeshBuilder {
+    pub fn new() -> Self {
+        Self {
+            subdivisions: 128,
+            radius: 1.0,
+            exaggeration: 0.05,
+            gradient: TerrainGradient::default(),
+            biomes: None,
+        }
+    }
+
+    /// Функция позволяющая указать число делений ребра каждой грани куба
+    /// или участка в [`PlanetMeshBuilder::build_chunk`].
+    pub fn set_subdivisions(mut self, subdivisions: usize) -> Self {
+        self.subdivisions = subdivisions.max(1);
+        self
+    }
+
+    /// Функция позволяющая указать радиус планеты на уровне высоты 0.0.
+    pub fn set_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    /// Функция позволяющая указать вертикальное преувеличение рельефа:
+    /// вершина смещается на `radius * exaggeration * elevation`.
+    pub fn set_exaggeration(mut self, exaggeration: f32) -> Self {
+        self.exaggeration = exaggeration;
+        self
+    }
+
+    /// Функция позволяющая раскрасить вершины по биомам вместо градиента высот.
+    /// Крутизна склона для классификатора берется из нормалей сетки.
+    pub fn set_biomes(mut self, classifier: BiomeClassifier, palette: BiomePalette) -> Self {
+        self.biomes = Some((classifier, palette));
+        self
+    }
+
+    /// Построение сетки планеты `planet`.
+    pub fn build(&self, planet: &Planet) -> Mesh {
+        let n = self.subdivisions;
+        let (keys, indices) = cube_grid(n);
+
+        let mut data = MeshData::default();
+        for key in &keys {
+            let point = key.map(|k| (2.0 * k as f64 - n as f64) / n as f64);
+            data.push_vertex(self, planet, cube_to_sphere(point), Vec3::ZERO);
+        }
+        data.indices = indices;
+
+        // Сглаженные нормали: сумма нормалей треугольников, взвешенных по площади
+        let mut normals = vec![Vec3::ZERO; data.positions.len()];
+        for triangle in data.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| Vec3::from(data.positions[triangle[k] as usize]));
+            let normal = (b - a).cross(c - a);
+            for index in triangle {
+                normals[*index as usize] += normal;
+            }
+        }
+        data.normals = normals.iter().map(|n| n.normalize().to_array()).collect();
+        data.paint(self, planet);
+
+        data.into_mesh()
+    }
+
+    /// Построение сетки участка `chunk` из `subdivisions` x `subdivisions` квадратов.
+    ///
+    /// Нормаль вершины зависит только от ее направления и уровня участка, поэтому
+    /// у соседних участков одного уровня нормали на общем ребре совпадают. Щели
+    /// между участками разных уровней закрывает юбка: полоса вдоль краев участка,
+    /// опущенная вниз на `radius * exaggeration`.
+    pub fn build_chunk(&self, planet: &Planet, chunk: ChunkId) -> Mesh {
+        let n = self.subdivisions;
+        let spacing = chunk.size() / n as f64;
+
+        let mut data = MeshData::default();
+        for j in 0..=n {
+            for i in 0..=n {
+                let direction = chunk.point(i as f64 / n as f64, j as f64 / n as f64);
+                let normal = self.surface_normal(planet, direction, spacing);
+                data.push_vertex(self, planet, direction, normal);
+            }
+        }
+
+        data.paint(self, planet);
+
+        let index = |i: usize, j: usize| (j * (n + 1) + i) as u32;
+        for j in 0..n {
+            for i in 0..n {
+                let quad = [
+                    index(i, j),
+                    index(i + 1, j),
+                    index(i + 1, j + 1),
+                    index(i, j + 1),
+                ];
+                data.indices
+                    .extend([quad[0], quad[1], quad[2], quad[0], quad[2], quad[3]]);
+            }
+        }
+
+        // Край участка по кругу
+        let border: Vec<u32> = (0..n)
+            .map(|i| index(i, 0))
+            .chain((0..n).map(|j| index(n, j)))
+            .chain((0..n).map(|i| index(n - i, n)))
+            .chain((0..n).map(|j| index(0, n - j)))
+            .collect();
+
+        let depth = self.radius * self.exaggeration;
+        let skirt: Vec<u32> = border
+            .iter()
+            .map(|vertex| {
+                let position = Vec3::from(data.positions[*vertex as usize]);
+                let lowered = position - position.normalize() * depth;
+                data.push_copy(*vertex as usize, lowered.to_array())
+            })
+            .collect();
+
+        // Юбка видна с обеих сторон, так что ее обход не важен
+        for k in 0..border.len() {
+            let next = (k + 1) % border.len();
+            let (a, b, lower_a, lower_b) = (border[k], border[next], skirt[k], skirt[next]);
+            data.indices.extend([a, b, lower_b, a, lower_b, lower_a]);
+            data.indices.extend([a, lower_b, b, a, lower_a, lower_b]);
+        }
+
+        data.into_mesh()
+    }
+
+    /// Построение сетки поверхности моря: сферы на высоте `sea_level`.
+    pub fn build_sea(&self, params: &PlanetParams) -> Mesh {
+        let n = self.subdivisions;
+        let (keys, indices) = cube_grid(n);
+
+        let mut data = MeshData::default();
+        for key in &keys {
+            let point = key.map(|k| (2.0 * k as f64 - n as f64) / n as f64);
+            let direction = cube_to_sphere(point);
+            data.push_level(
+                self,
+                direction,
+                direction_lat_lon(direction),
+                params.sea_level,
+            );
+        }
+        data.indices = indices;
+
+        data.into_mesh()
+    }
+
+    /// Построение сетки зеркал озер вида `kind`: каждый пиксель озера на карте
+    /// становится четырехугольником на уровне воды.
+    /// `None`, если таких озер нет.
+    pub fn build_lakes(&self, lakes: &LakeMap, kind: LakeKind) -> Option<Mesh> {
+        let bounds = lakes.bounds();
+        let pixel_width = bounds.lon_extent() / lakes.width() as f64;
+        let pixel_height = bounds.lat_extent() / lakes.height() as f64;
+
+        let mut data = MeshData::default();
+        for lake in lakes.lakes().iter().filter(|lake| lake.kind == kind) {
+            for (x, y) in &lake.cells {
+                let (north, west) = (
+                    bounds.north - *y as f64 * pixel_height,
+                    bounds.west + *x as f64 * pixel_width,
+                );
+                let (south, east) = (north - pixel_height, west + pixel_width);
+
+                let first = data.positions.len() as u32;
+                for (lat, lon) in [(north, west), (south, west), (south, east), (north, east)] {
+                    data.push_level(self, lat_lon_direction(lat, lon), (lat, lon), lake.level);
+                }
+
+                // Северо-запад, юго-запад, юго-восток, северо-восток:
+                // обход против часовой стрелки, если смотреть снаружи
+                data.indices
+                    .extend([0, 1, 2, 0, 2, 3].map(|corner| first + corner));
+            }
+        }
+
+        (!data.indices.is_empty()).then(|| data.into_mesh())
+    }
+
+    /// Точка поверхности планеты в направлении `direction` с учетом преувеличения рельефа.
+    fn surface(&self, planet: &Planet, direction: [f64; 3]) -> (Vec3, f64) {
+        let (lat, lon) = direction_lat_lon(direction);
+        let elevation = planet.elevation(lat, lon);
+        let radius = self.radius * (1.0 + self.exaggeration * elevation as f32);
+
+        (
+            Vec3::from(direction.map(|axis| axis as f32)) * radius,
+            elevation,
+        )
+    }
+
+    /// Нормаль поверхности по четырем соседним точкам на расстоянии `spacing`
+    /// к востоку, западу, северу и югу от направления `direction`.
+    fn surface_normal(&self, planet: &Planet, direction: [f64; 3], spacing: f64) -> Vec3 {
+        let direction = DVec3::from(direction);
+
+        // На полюсах восток не определен, подойдет любое направление вдоль поверхности
+        let east = DVec3::Y
+            .cross(direction)
+            .try_normalize()
+            .unwrap_or(DVec3::X);
+        let north = direction.cross(east);
+
+        let neighbour = |offset: DVec3| {
+            let direction = (direction + offset * spacing).normalize();
+            self.surface(planet, direction.to_array()).0
+        };
+        let (east, west, north, south) = (
+            neighbour(east),
+            neighbour(-east),
+            neighbour(north),
+            neighbour(-north),
+        );
+
+        (east - west).cross(north - south).normalize()
+    }
+}
+
+/// Данные сетки до передачи в Bevy.
+#[derive(Default)]
+struct MeshData {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    colors: Vec<u32>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+    /// Широта, долгота и высота вершин, пока они не раскрашены.
+    samples: Vec<(f64, f64, f64)>,
+}
+
+impl MeshData {
+    /// Вершина на поверхности планеты в направлении `direction`.
+    fn push_vertex(
+        &mut self,
+        builder: &PlanetMeshBuilder,
+        planet: &Planet,
+        direction: [f64; 3],
+        normal: Vec3,
+    ) {
+        let (lat, lon) = direction_lat_lon(direction);
+        let (position, elevation) = builder.surface(planet, direction);
+
+        self.positions.push(position.to_array());
+        self.normals.push(normal.to_array());
+        self.samples.push((lat, lon, elevation));
+        self.uvs.push([
+            ((lon + 180.0) / 360.0) as f32,
+            ((90.0 - lat) / 180.0) as f32,
+        ]);
+    }
+
+    /// Вершина ровной поверхности воды на высоте `level` в направлении `direction`,
+    /// которое указывает на точку (`lat`, `lon`).
+    fn push_level(
+        &mut self,
+        builder: &PlanetMeshBuilder,
+        direction: [f64; 3],
+        (lat, lon): (f64, f64),
+        level: f64,
+    ) {
+        let direction = Vec3::from(direction.map(|axis| axis as f32));
+        let radius = builder.radius * (1.0 + builder.exaggeration * level as f32);
+
+        self.positions.push((direction * radius).to_array());
+        self.normals.push(direction.to_array());
+        self.colors.push(u32::MAX);
+        self.samples.push((lat, lon, level));
+        self.uvs.push([
+            ((lon + 180.0) / 360.0) as f32,
+            ((90.0 - lat) / 180.0) as f32,
+        ]);
+    }
+
+    /// Цвета всех вершин. Нормали должны быть уже посчитаны.
+    fn paint(&mut self, builder: &PlanetMeshBuilder, planet: &Planet) {
+        self.colors = self
+            .samples
+            .iter()
+            .zip(&self.positions)
+            .zip(&self.normals)
+            .map(|((&(lat, lon, elevation), position), normal)| {
+                let [r, g, b] = match &builder.biomes {
+                    Some((classifier, palette)) => {
+                        let tilt = Vec3::from(*position).normalize().dot(Vec3::from(*normal));
+                        let biome = classifier.classify(
+                            elevation,
+                            planet.sample_layer(TerrainLayer::BadlandsMask, lat, lon),
+                            lat,
+                            slope_from_tilt(tilt as f64, builder.exaggeration as f64),
+                        );
+                        palette.color(biome)
+                    }
+                    None => builder.gradient.color(elevation, planet.params()),
+                };
+
+                u32::from_le_bytes([r, g, b, 255])
+            })
+            .collect();
+    }
+
+    /// Копия вершины `vertex` в другой точке. Возвращает индекс копии.
+    fn push_copy(&mut self, vertex: usize, position: [f32; 3]) -> u32 {
+        self.positions.push(position);
+        self.normals.push(self.normals[vertex]);
+        self.colors.push(self.colors[vertex]);
+        self.uvs.push(self.uvs[vertex]);
+        self.positions.len() as u32 - 1
+    }
+
+    /// Треугольники на антимеридиане получают копии вершин с u + 1,
+    /// чтобы текстура не растягивалась через всю карту.
+    fn split_antimeridian(&mut self) {
+        let mut seam = HashMap::new();
+
+        for triangle in 0..self.indices.len() / 3 {
+            let corners = triangle * 3..triangle * 3 + 3;
+            let (min, max) =
+                self.indices[corners.clone()]
+                    .iter()
+                    .fold((1.0f32, 0.0f32), |(min, max), index| {
+                        let u = self.uvs[*index as usize][0];
+                        (min.min(u), max.max(u))
+                    });
+            if max - min <= 0.5 {
+                continue;
+            }
+
+            for corner in corners {
+                let vertex = self.indices[corner] as usize;
+                if self.uvs[vertex][0] < 0.5 {
+                    self.indices[corner] = *seam.entry(vertex).or_insert_with(|| {
+                        let copy = self.push_copy(vertex, self.positions[vertex]);
+                        self.uvs[copy as usize][0] += 1.0;
+                        copy
+                    });
+                }
+            }
+        }
+    }
+
+    fn into_mesh(mut self) -> Mesh {
+        self.split_antimeridian();
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        mesh
+    }
+}
+
+/// Вершины и треугольники куба, каждая грань которого разбита на `n` x `n` квадратов.
+///
+/// Вершина определяется целочисленными координатами на кубе [0, n]^3,
+/// так что вершины на ребрах граней находятся один раз.
+fn cube_grid(n: usize) -> (Vec<[usize; 3]>, Vec<u32>) {
+    let mut vertices = HashMap::new();
+    let mut keys = Vec::new();
+    let mut indices = Vec::with_capacity(6 * n * n * 6);
+
+    for axis in 0..3 {
+        for side in [0, n] {
+            let mut vertex = |i: usize, j: usize| {
+                let mut key = [0; 3];
+                key[axis] = side;
+                key[(axis + 1) % 3] = i;
+                key[(axis + 2) % 3] = j;
+
+                *vertices.entry(key).or_insert_with(|| {
+                    keys.push(key);
+                    keys.len() as u32 - 1
+                })
+            };
+
+            for i in 0..n {
+                for j in 0..n {
+                    let quad = [
+                        vertex(i, j),
+                        vertex(i + 1, j),
+                        vertex(i + 1, j + 1),
+                        vertex(i, j + 1),
+                    ];
+
+                    // Обход против часовой стрелки, если смотреть снаружи
+                    if side == n {
+                        indices.extend([quad[0], quad[1], quad[2], quad[0], quad[2], quad[3]]);
+                    } else {
+                        indices.extend([quad[0], quad[2], quad[1], quad[0], quad[3], quad[2]]);
+                    }
+                }
+            }
+        }
+    }
+
+    (keys, indices)
+}
+
+/// Направление на точку (`lat`, `lon`), обратное [`direction_lat_lon`].
+fn lat_lon_direction(lat: f64, lon: f64) -> [f64; 3] {
+    let (lat, lon) = (lat.to_radians(), lon.to_radians());
+    [lat.cos() * lon.cos(), lat.sin(), -lat.cos() * lon.sin()]
+}
+
+/// Широта и долгота направления в градусах.
+/// Y смотрит на север, восток против часовой стрелки, если смотреть с севера.
+fn direction_lat_lon([x, y, z]: [f64; 3]) -> (f64, f64) {
+    (
+        y.clamp(-1.0, 1.0).asin().to_degrees(),
+        (-z).atan2(x).to_degrees(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::{MeshVertexAttribute, VertexAttributeValues};
+    use unistone::world::{CubeFace, WorldBuilder};
+
+    use super::*;
+
+    fn vectors(mesh: &Mesh, attribute: MeshVertexAttribute) -> Vec<Vec3> {
+        match mesh.attribute(attribute) {
+            Some(VertexAttributeValues::Float32x3(values)) => {
+                values.iter().map(|value| Vec3::from(*value)).collect()
+            }
+            _ => panic!("mesh has no vector attribute"),
+        }
+    }
+
+    fn planet() -> Planet {
+        WorldBuilder::new().set_seed(7).build().unwrap()
+    }
+
+    /// Вершины на ребрах куба общие для граней: каждая точка сферы встречается
+    /// один раз, кроме копий на антимеридиане с теми же нормалями.
+    #[test]
+    fn cube_faces_share_edge_vertices() {
+        let n = 4;
+        let mesh = PlanetMeshBuilder::new()
+            .set_subdivisions(n)
+            .build(&planet());
+        let positions = vectors(&mesh, Mesh::ATTRIBUTE_POSITION);
+        let normals = vectors(&mesh, Mesh::ATTRIBUTE_NORMAL);
+
+        let mut distinct: Vec<(Vec3, Vec3)> = Vec::new();
+        for (position, normal) in positions.into_iter().zip(normals) {
+            match distinct
+                .iter()
+                .find(|(other, _)| other.distance(position) < 1e-6)
+            {
+                Some((_, other)) => assert_eq!(*other, normal),
+                None => distinct.push((position, normal)),
+            }
+        }
+
+        assert_eq!(distinct.len(), 6 * n * n + 2);
+    }
+
+    /// Участки соседних граней совпадают на общем ребре по позициям и нормалям.
+    #[test]
+    fn adjacent_face_chunks_match_on_edge() {
+        let n = 4;
+        let planet = planet();
+        let builder = PlanetMeshBuilder::new().set_subdivisions(n);
+
+        // Сетка участка без юбки и копий на антимеридиане
+        let grid = |face| {
+            let mesh = builder.build_chunk(&planet, ChunkId::root(face));
+            let positions = vectors(&mesh, Mesh::ATTRIBUTE_POSITION);
+            let normals = vectors(&mesh, Mesh::ATTRIBUTE_NORMAL);
+            positions
+                .into_iter()
+                .zip(normals)
+                .take((n + 1) * (n + 1))
+                .collect::<Vec<_>>()
+        };
+        let (first, second) = (grid(CubeFace::PosX), grid(CubeFace::PosY));
+
+        let mut shared = 0;
+        for (position, normal) in &first {
+            if let Some((_, other)) = second
+                .iter()
+                .find(|(other, _)| other.distance(*position) < 1e-5)
+            {
+                assert!(normal.distance(*other) < 1e-4, "{} != {}", normal, other);
+                shared += 1;
+            }
+        }
+
+        assert_eq!(shared, n + 1);
+    }
+}
@@ -0,0 +1,235 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use bevy::core::{Time, Timer};
+use bevy::log::{error, info};
+use bevy::prelude::{
+    App, Assets, Commands, Component, Entity, EventReader, EventWriter, Image, Mesh, PbrBundle,
+    Plugin, Query, Res, ResMut, StandardMaterial, SystemSet, Transform, With,
+};
+use bevy::render::render_resource::{
+    AddressMode, Extent3d, FilterMode, SamplerDescriptor, TextureDimension, TextureFormat,
+};
+use unistone::world::{
+    BiomeClassifier, BiomePalette, MapImage, PlanetParams, PresetError, PresetLibrary, WorldBuilder,
+};
+
+use super::planet_mesh::PlanetMeshBuilder;
+use super::worldgen::{GenerateWorld, WorldGenerated};
+
+/// Настройки планеты на сцене.
+///
+/// Файл `params_path` — пресет параметров планеты в TOML. Пока игра запущена,
+/// файл проверяется каждые `poll_interval` секунд, и после каждого сохранения
+/// планета строится заново в фоне.
+#[derive(Debug, Clone)]
+pub struct TerrainSettings {
+    pub params_path: PathBuf,
+    pub seed: u32,
+    /// Размер карты высот, из которой строится текстура.
+    pub resolution: (usize, usize),
+    /// Число делений ребра каждой грани куба в сетке планеты.
+    /// Без него единая сетка не строится, например, когда ландшафт
+    /// показывается участками [`LodTerrainPlugin`](super::lod::LodTerrainPlugin).
+    pub subdivisions: Option<usize>,
+    pub radius: f32,
+    /// Во сколько раз рельеф выше, чем в планетарных единицах высоты относительно радиуса.
+    pub exaggeration: f32,
+    /// Раскрашивать вершины сетки по биомам вместо градиента высот.
+    pub biomes: bool,
+    pub poll_interval: f32,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            params_path: PathBuf::from("assets/worldgen/presets/earthlike.toml"),
+            seed: rand::random(),
+            resolution: (512, 256),
+            subdivisions: Some(128),
+            radius: 1.0,
+            exaggeration: 0.05,
+            biomes: false,
+            poll_interval: 0.5,
+        }
+    }
+}
+
+/// Маркер сущности с ландшафтом планеты.
+#[derive(Component)]
+pub struct Terrain;
+
+/// Время последнего изменения файла параметров, по которому строилась планета.
+struct ParamsWatcher {
+    timer: Timer,
+    modified: Option<SystemTime>,
+    started: bool,
+}
+
+/// Ландшафт планеты с перезагрузкой параметров из файла.
+/// Генерацию выполняет [`WorldGenPlugin`](super::worldgen::WorldGenPlugin),
+/// его нужно подключить отдельно.
+pub struct TerrainPlugin;
+
+impl TerrainPlugin {
+    /// Запрос генерации, если файл параметров изменился с прошлой проверки.
+    fn watch_params(
+        time: Res<Time>,
+        settings: Res<TerrainSettings>,
+        mut watcher: ResMut<ParamsWatcher>,
+        mut requests: EventWriter<GenerateWorld>,
+    ) {
+        if !watcher.timer.tick(time.delta()).just_finished() && watcher.started {
+            return;
+        }
+
+        let modified = fs::metadata(&settings.params_path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if watcher.started && modified == watcher.modified {
+            return;
+        }
+
+        watcher.started = true;
+        watcher.modified = modified;
+
+        info!(
+            "loading planet params from {}",
+            settings.params_path.display()
+        );
+        match load_builder(&settings) {
+            Ok(builder) => requests.send(GenerateWorld {
+                mesh: settings.subdivisions.map(|subdivisions| {
+                    planet_mesh_builder(&settings, builder.params()).set_subdivisions(subdivisions)
+                }),
+                builder,
+                width: settings.resolution.0,
+                height: settings.resolution.1,
+            }),
+            Err(error) => error!("{}, keeping the old terrain", error),
+        }
+    }
+
+    /// Замена сетки и материала планеты новым миром.
+    /// При ошибке генерации события нет, и остается прежний ландшафт.
+    fn swap_terrain(
+        mut commands: Commands,
+        mut generated: EventReader<WorldGenerated>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut images: ResMut<Assets<Image>>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+        query: Query<Entity, With<Terrain>>,
+    ) {
+        // Нужен только последний мир с сеткой
+        let (world, mesh) = match generated
+            .iter()
+            .rev()
+            .find_map(|WorldGenerated(world)| Some((world, world.mesh.as_ref()?)))
+        {
+            Some(generated) => generated,
+            None => return,
+        };
+
+        let mesh = meshes.add(mesh.clone());
+        let material = materials.add(StandardMaterial {
+            base_color_texture: Some(images.add(terrain_image(&world.map))),
+            perceptual_roughness: 0.9,
+            ..Default::default()
+        });
+
+        // Старые сетка и материал освобождаются вместе с последним дескриптором
+        match query.get_single() {
+            Ok(entity) => {
+                commands.entity(entity).insert(mesh).insert(material);
+            }
+            Err(_) => {
+                commands
+                    .spawn_bundle(PbrBundle {
+                        mesh,
+                        material,
+                        transform: Transform::identity(),
+                        ..Default::default()
+                    })
+                    .insert(Terrain);
+            }
+        }
+
+        info!("planet terrain updated");
+    }
+}
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = app
+            .world
+            .get_resource_or_insert_with(TerrainSettings::default)
+            .clone();
+
+        app.insert_resource(ParamsWatcher {
+            timer: Timer::from_seconds(settings.poll_interval, true),
+            modified: None,
+            started: false,
+        })
+        .add_system_set(
+            SystemSet::new()
+                .label(super::Systems::Terrain)
+                .with_system(TerrainPlugin::watch_params)
+                .with_system(TerrainPlugin::swap_terrain),
+        );
+    }
+}
+
+/// Конструктор мира по файлу параметров. Пресет может наследоваться от встроенных.
+fn load_builder(settings: &TerrainSettings) -> Result<WorldBuilder, PresetError> {
+    let mut library = PresetLibrary::builtin();
+    let name = library.load(&settings.params_path)?;
+
+    WorldBuilder::new()
+        .set_seed(settings.seed)
+        .set_preset(&library, &name)
+}
+
+/// Построитель сетки планеты с параметрами `params` по настройкам сцены.
+pub(super) fn planet_mesh_builder(
+    settings: &TerrainSettings,
+    params: &PlanetParams,
+) -> PlanetMeshBuilder {
+    let builder = PlanetMeshBuilder::new()
+        .set_radius(settings.radius)
+        .set_exaggeration(settings.exaggeration);
+
+    if settings.biomes {
+        builder.set_biomes(BiomeClassifier::new(params), BiomePalette::default())
+    } else {
+        builder
+    }
+}
+
+/// Текстура из цветной карты. По горизонтали повторяется,
+/// потому что UV сетки на антимеридиане выходят за 1.0.
+pub(super) fn terrain_image(map: &MapImage) -> Image {
+    let data = map
+        .pixels()
+        .iter()
+        .flat_map(|[r, g, b]| [*r, *g, *b, 255])
+        .collect();
+
+    let mut image = Image::new(
+        Extent3d {
+            width: map.width() as u32,
+            height: map.height() as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    image.sampler_descriptor = SamplerDescriptor {
+        address_mode_u: AddressMode::Repeat,
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        ..Default::default()
+    };
+    image
+}
@@ -0,0 +1,123 @@
+use bevy::log::info;
+use bevy::prelude::{
+    AlphaMode, App, Assets, Color, Commands, Component, DespawnRecursiveExt, Entity, EventReader,
+    Mesh, PbrBundle, Plugin, Query, Res, ResMut, StandardMaterial, SystemSet, Transform, With,
+};
+use strum::IntoEnumIterator;
+use unistone::world::LakeKind;
+
+use super::planet_mesh::PlanetMeshBuilder;
+use super::terrain::TerrainSettings;
+use super::worldgen::WorldGenerated;
+
+/// Настройки водной поверхности.
+///
+/// Радиус и преувеличение рельефа берутся из [`TerrainSettings`].
+#[derive(Debug, Clone)]
+pub struct WaterSettings {
+    /// Число делений ребра каждой грани куба в сетке моря.
+    pub subdivisions: usize,
+    pub sea_color: Color,
+    pub fresh_lake_color: Color,
+    pub salt_lake_color: Color,
+}
+
+impl Default for WaterSettings {
+    fn default() -> Self {
+        Self {
+            subdivisions: 48,
+            sea_color: Color::rgba(0.05, 0.2, 0.45, 0.6),
+            fresh_lake_color: Color::rgba(0.15, 0.4, 0.7, 0.75),
+            salt_lake_color: Color::rgba(0.6, 0.78, 0.8, 0.85),
+        }
+    }
+}
+
+impl WaterSettings {
+    fn lake_color(&self, kind: LakeKind) -> Color {
+        match kind {
+            LakeKind::Fresh => self.fresh_lake_color,
+            LakeKind::Salt => self.salt_lake_color,
+        }
+    }
+}
+
+/// Водная поверхность: море или озера.
+#[derive(Component)]
+pub enum Water {
+    Sea,
+    Lakes,
+}
+
+/// Полупрозрачная поверхность моря на уровне `sea_level` и зеркала озер
+/// нового мира. Мир строит [`WorldGenPlugin`](super::worldgen::WorldGenPlugin).
+pub struct WaterPlugin;
+
+impl WaterPlugin {
+    /// Замена водной поверхности новым миром.
+    fn swap_water(
+        mut commands: Commands,
+        settings: Res<WaterSettings>,
+        terrain_settings: Res<TerrainSettings>,
+        mut generated: EventReader<WorldGenerated>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+        query: Query<Entity, With<Water>>,
+    ) {
+        let world = match generated.iter().last() {
+            Some(WorldGenerated(world)) => world,
+            None => return,
+        };
+
+        for entity in query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+
+        let builder = PlanetMeshBuilder::new()
+            .set_subdivisions(settings.subdivisions)
+            .set_radius(terrain_settings.radius)
+            .set_exaggeration(terrain_settings.exaggeration);
+
+        let mut bodies = vec![(
+            Water::Sea,
+            builder.build_sea(&world.params),
+            settings.sea_color,
+        )];
+        for kind in LakeKind::iter() {
+            if let Some(mesh) = builder.build_lakes(&world.lakes, kind) {
+                bodies.push((Water::Lakes, mesh, settings.lake_color(kind)));
+            }
+        }
+
+        for (water, mesh, color) in bodies {
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: meshes.add(mesh),
+                    material: materials.add(StandardMaterial {
+                        base_color: color,
+                        perceptual_roughness: 0.2,
+                        alpha_mode: AlphaMode::Blend,
+                        ..Default::default()
+                    }),
+                    transform: Transform::identity(),
+                    ..Default::default()
+                })
+                .insert(water);
+        }
+
+        info!("water updated, {} lakes", world.lakes.lakes().len());
+    }
+}
+
+impl Plugin for WaterPlugin {
+    fn build(&self, app: &mut App) {
+        app.world
+            .get_resource_or_insert_with(TerrainSettings::default);
+
+        app.init_resource::<WaterSettings>().add_system_set(
+            SystemSet::new()
+                .label(super::Systems::Water)
+                .with_system(WaterPlugin::swap_water),
+        );
+    }
+}
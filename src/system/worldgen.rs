@@ -0,0 +1,216 @@
+use bevy::log::{error, info};
+use bevy::prelude::{App, EventReader, EventWriter, Mesh, Plugin, Res, ResMut, SystemSet};
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use crossbeam_channel::{Receiver, Sender};
+use futures_lite::future;
+use unistone::world::{
+    ChunkedSampler, Climate, ClimateConfig, Heightmap, LakeConfig, LakeMap, LightSource, MapImage,
+    MapRenderer, PlanetParams, WorldBuilder, WorldParamError,
+};
+
+use super::planet_mesh::PlanetMeshBuilder;
+
+/// Запрос на генерацию мира.
+///
+/// Новый запрос заменяет генерацию, которая еще не завершилась: ее результат
+/// отбрасывается, но фоновая задача работает до конца.
+#[derive(Debug, Clone)]
+pub struct GenerateWorld {
+    pub builder: WorldBuilder,
+    /// Размер карты высот в пикселях.
+    pub width: usize,
+    pub height: usize,
+    /// Построитель сетки планеты. Без него сетка не строится.
+    pub mesh: Option<PlanetMeshBuilder>,
+}
+
+/// Этап генерации мира.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorldGenStage {
+    /// Проверка параметров и заполнение карты высот во всех потоках.
+    SampleHeightmap,
+    /// Расчет климата и поиск озер.
+    DetectLakes,
+    /// Построение цветной карты.
+    RenderMap,
+    /// Построение сетки планеты.
+    BuildMesh,
+}
+
+/// Ход генерации мира.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldGenProgress {
+    pub stage: WorldGenStage,
+    /// Готовая доля текущего этапа от 0.0 до 1.0.
+    pub fraction: f32,
+}
+
+/// Результат генерации мира.
+///
+/// Сама [`unistone::world::Planet`] не покидает фоновую задачу,
+/// наружу передаются только построенные по ней данные.
+#[derive(Debug, Clone)]
+pub struct GeneratedWorld {
+    /// Конструктор, по которому построен мир. Нужен, чтобы строить участки планеты позже.
+    pub builder: WorldBuilder,
+    pub params: PlanetParams,
+    pub heightmap: Heightmap,
+    pub lakes: LakeMap,
+    pub map: MapImage,
+    pub mesh: Option<Mesh>,
+}
+
+/// Генерация мира завершена.
+#[derive(Debug, Clone)]
+pub struct WorldGenerated(pub GeneratedWorld);
+
+/// Генерация мира завершилась ошибкой.
+#[derive(Debug, Clone, Copy)]
+pub struct WorldGenFailed(pub WorldParamError);
+
+/// Фоновая генерация и канал ее хода.
+struct Generation {
+    task: Task<Result<GeneratedWorld, WorldParamError>>,
+    progress: Receiver<WorldGenProgress>,
+}
+
+/// Текущая генерация. Одновременно идет не больше одной.
+#[derive(Default)]
+struct WorldGenTask(Option<Generation>);
+
+/// Генерация мира на `AsyncComputeTaskPool`, чтобы не останавливать кадры.
+pub struct WorldGenPlugin;
+
+impl WorldGenPlugin {
+    /// Запуск генерации по последнему запросу кадра.
+    fn start(
+        pool: Res<AsyncComputeTaskPool>,
+        mut requests: EventReader<GenerateWorld>,
+        mut task: ResMut<WorldGenTask>,
+    ) {
+        let request = match requests.iter().last() {
+            Some(request) => request.clone(),
+            None => return,
+        };
+
+        info!("generating world with seed {}", request.builder.seed());
+
+        // Результат прежней задачи больше не нужен, она доработает впустую
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        task.0 = Some(Generation {
+            task: pool.spawn(async move { generate(request, &sender) }),
+            progress: receiver,
+        });
+    }
+
+    /// Передача хода и результата генерации в события.
+    fn poll(
+        mut task: ResMut<WorldGenTask>,
+        mut progress: EventWriter<WorldGenProgress>,
+        mut generated: EventWriter<WorldGenerated>,
+        mut failed: EventWriter<WorldGenFailed>,
+    ) {
+        let result = match task.0.as_mut() {
+            Some(generation) => {
+                progress.send_batch(generation.progress.try_iter());
+
+                match future::block_on(future::poll_once(&mut generation.task)) {
+                    Some(result) => result,
+                    None => return,
+                }
+            }
+            None => return,
+        };
+        task.0 = None;
+
+        match result {
+            Ok(world) => generated.send(WorldGenerated(world)),
+            Err(error) => failed.send(WorldGenFailed(error)),
+        }
+    }
+
+    /// Запись результатов генерации в журнал.
+    fn log(mut generated: EventReader<WorldGenerated>, mut failed: EventReader<WorldGenFailed>) {
+        for WorldGenerated(world) in generated.iter() {
+            info!(
+                "world with seed {} generated, elevation {:.3}..{:.3}",
+                world.builder.seed(),
+                world.heightmap.min(),
+                world.heightmap.max()
+            );
+        }
+
+        for WorldGenFailed(error) in failed.iter() {
+            error!("world generation failed: {}", error);
+        }
+    }
+}
+
+impl Plugin for WorldGenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GenerateWorld>()
+            .add_event::<WorldGenProgress>()
+            .add_event::<WorldGenerated>()
+            .add_event::<WorldGenFailed>()
+            .init_resource::<WorldGenTask>()
+            .add_system_set(
+                SystemSet::new()
+                    .label(super::Systems::WorldGen)
+                    .with_system(WorldGenPlugin::start)
+                    .with_system(WorldGenPlugin::poll)
+                    .with_system(WorldGenPlugin::log),
+            );
+    }
+}
+
+/// Генерация мира целиком. Выполняется в фоновой задаче.
+fn generate(
+    request: GenerateWorld,
+    progress: &Sender<WorldGenProgress>,
+) -> Result<GeneratedWorld, WorldParamError> {
+    // Получатель мог быть уже уничтожен вместе с отмененной задачей
+    let report = |stage, fraction| {
+        let _ = progress.send(WorldGenProgress { stage, fraction });
+    };
+
+    let params = *request.builder.params();
+
+    report(WorldGenStage::SampleHeightmap, 0.0);
+    let heightmap = ChunkedSampler::new(request.builder.clone()).render_heightmap_with_progress(
+        request.width,
+        request.height,
+        |fraction| report(WorldGenStage::SampleHeightmap, fraction),
+    )?;
+
+    report(WorldGenStage::DetectLakes, 0.0);
+    let climate = Climate::simulate(&heightmap, &params, &ClimateConfig::default());
+    let lakes = LakeMap::detect_with_climate(&heightmap, &params, &LakeConfig::default(), &climate);
+    report(WorldGenStage::DetectLakes, 1.0);
+
+    report(WorldGenStage::RenderMap, 0.0);
+    let map = MapRenderer::new(&params)
+        .enable_light(LightSource::default())
+        .set_lakes(lakes.clone())
+        .render(&heightmap)
+        .expect("lakes are detected on the same heightmap");
+    report(WorldGenStage::RenderMap, 1.0);
+
+    let mesh = match request.mesh {
+        Some(mesh_builder) => {
+            report(WorldGenStage::BuildMesh, 0.0);
+            let mesh = mesh_builder.build(&request.builder.clone().build()?);
+            report(WorldGenStage::BuildMesh, 1.0);
+            Some(mesh)
+        }
+        None => None,
+    };
+
+    Ok(GeneratedWorld {
+        builder: request.builder,
+        params,
+        heightmap,
+        lakes,
+        map,
+        mesh,
+    })
+}